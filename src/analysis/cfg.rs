@@ -0,0 +1,49 @@
+use crate::document::{ByteRange, Doc};
+use crate::utils::froggy_helpers::program_instructions;
+use crate::utils::tree_sitter_helpers::node_byte_range;
+
+// Control flow between the top-level instructions of a program, label definitions included
+#[derive(Clone, Debug, Default)]
+pub struct Cfg {
+    pub nodes: Vec<CfgNode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CfgNode {
+    pub kind: &'static str,
+    pub range: ByteRange,
+    // Instructions control can continue at; empty where the program ends
+    pub succs: Vec<usize>,
+}
+
+impl Cfg {
+    pub fn build(doc: &Doc) -> Self {
+        let instrs = program_instructions(&doc.tree);
+        let bytes = doc.text.as_bytes();
+        // Jumps go to the definition go-to-definition picks
+        let target = |node: tree_sitter::Node| {
+            let name = node.child_by_field_name("target")?.utf8_text(bytes).ok()?;
+            let def = doc.index().label_defs.get(name)?;
+            instrs.iter().position(|n| n.start_byte() == def.start)
+        };
+
+        let nodes = instrs
+            .iter()
+            .enumerate()
+            .map(|(i, &node)| {
+                let next = (i + 1 < instrs.len()).then_some(i + 1);
+                let succs = match node.kind() {
+                    "hop" => target(node).into_iter().collect(),
+                    "leap" => next.into_iter().chain(target(node)).collect(),
+                    _ => next.into_iter().collect(),
+                };
+                CfgNode {
+                    kind: node.kind(),
+                    range: node_byte_range(node),
+                    succs,
+                }
+            })
+            .collect();
+        Self { nodes }
+    }
+}
@@ -0,0 +1,79 @@
+// Analyses derived from a document snapshot, each computed the first time it is asked
+// for and kept for as long as the snapshot. Queries only run what they depend on, so
+// hover or go-to-definition never pays for the lints, and an edit (a new snapshot)
+// starts from scratch only for the analyses that are asked for again.
+
+pub mod cfg;
+pub mod stack;
+
+use std::sync::{Mutex, OnceLock};
+
+use tower_lsp::lsp_types::Diagnostic;
+
+use crate::diagnostics::{collect_diagnostics, compile_diagnostics};
+use crate::document::{Doc, Index};
+use crate::lint::LintConfig;
+use cfg::Cfg;
+use stack::StackAnalysis;
+
+#[derive(Debug, Default)]
+pub struct Queries {
+    index: OnceLock<Index>,
+    cfg: OnceLock<Cfg>,
+    stack: OnceLock<StackAnalysis>,
+    // Diagnostics also depend on the lint config they were computed with
+    diagnostics: Mutex<Option<(LintConfig, Vec<Diagnostic>)>>,
+    compile: OnceLock<Vec<Diagnostic>>,
+}
+
+// Results carry over to a snapshot with the same text
+impl Clone for Queries {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index.clone(),
+            cfg: self.cfg.clone(),
+            stack: self.stack.clone(),
+            diagnostics: Mutex::new(self.diagnostics.lock().unwrap().clone()),
+            compile: self.compile.clone(),
+        }
+    }
+}
+
+impl Doc {
+    pub fn index(&self) -> &Index {
+        self.queries
+            .index
+            .get_or_init(|| Index::build(&self.tree, &self.text))
+    }
+
+    pub fn cfg(&self) -> &Cfg {
+        self.queries.cfg.get_or_init(|| Cfg::build(self))
+    }
+
+    pub fn stack_analysis(&self) -> &StackAnalysis {
+        self.queries
+            .stack
+            .get_or_init(|| StackAnalysis::build(self.index(), self.cfg()))
+    }
+
+    // Syntax errors and lints
+    pub fn diagnostics(&self, lints: &LintConfig) -> Vec<Diagnostic> {
+        let mut cached = self.queries.diagnostics.lock().unwrap();
+        if let Some((config, diags)) = &*cached
+            && config == lints
+        {
+            return diags.clone();
+        }
+        let diags = collect_diagnostics(&self.tree, self, lints);
+        *cached = Some((lints.clone(), diags.clone()));
+        diags
+    }
+
+    // Problems only a full compile finds
+    pub fn compile_diagnostics(&self) -> Vec<Diagnostic> {
+        self.queries
+            .compile
+            .get_or_init(|| compile_diagnostics(self))
+            .clone()
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::cfg::Cfg;
+use crate::document::{ByteRange, Index};
+use crate::utils::froggy_helpers::label_names;
+
+// Values an instruction pops and then pushes
+pub fn stack_effect(kind: &str) -> (u32, u32) {
+    match kind {
+        "plop" | "croak" => (0, 1),
+        "splash" | "leap" => (1, 0),
+        // RIBBIT prints the top of the stack without popping it
+        "ribbit" | "gulp" | "burp" => (1, 1),
+        "dup" => (1, 2),
+        "swap" => (2, 2),
+        "over" => (2, 3),
+        "add" | "sub" | "mul" | "div" | "equals" | "not_equal" | "less_than" | "greater_than"
+        | "less_eq" | "greater_eq" => (2, 1),
+        _ => (0, 0),
+    }
+}
+
+// What each labelled block does to the stack, by label name
+#[derive(Clone, Debug, Default)]
+pub struct StackAnalysis {
+    pub blocks: HashMap<String, BlockEffect>,
+}
+
+impl StackAnalysis {
+    pub fn build(index: &Index, cfg: &Cfg) -> Self {
+        let blocks = label_names(index)
+            .into_iter()
+            .filter_map(|name| {
+                let def = &index.label_defs[name];
+                let label = cfg.nodes.iter().position(|n| n.range.start == def.start)?;
+                Some((name.to_string(), block_effect(cfg, label)))
+            })
+            .collect();
+        Self { blocks }
+    }
+}
+
+// A block's declared stack effect, from a Forth-style `// ( a b -- sum )` comment
+// on the line above its LILY
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StackEffect {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    // The annotation comment
+    pub range: ByteRange,
+}
+
+impl StackEffect {
+    pub fn parse(comment: &str, range: ByteRange) -> Option<Self> {
+        let body = comment.strip_prefix("//")?.trim();
+        let (inputs, outputs) = body
+            .strip_prefix('(')?
+            .strip_suffix(')')?
+            .split_once("--")?;
+        let names = |s: &str| s.split_whitespace().map(str::to_string).collect();
+        Some(Self {
+            inputs: names(inputs),
+            outputs: names(outputs),
+            range,
+        })
+    }
+}
+
+impl fmt::Display for StackEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(")?;
+        for name in &self.inputs {
+            write!(f, " {name}")?;
+        }
+        write!(f, " --")?;
+        for name in &self.outputs {
+            write!(f, " {name}")?;
+        }
+        write!(f, " )")
+    }
+}
+
+// What a label's block does to the stack, from its LILY to where control leaves it:
+// a HOP, a LEAP that jumps, falling into the next label or the end of the program
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockEffect {
+    // Values from below the block's starting depth that it pops
+    pub consumes: u32,
+    // Change in depth on leaving the block; None if it differs between exits
+    pub net: Option<i64>,
+}
+
+fn block_effect(cfg: &Cfg, label: usize) -> BlockEffect {
+    let mut depth: i64 = 0;
+    let mut lowest: i64 = 0;
+    let mut exits = Vec::new();
+
+    for node in &cfg.nodes[label + 1..] {
+        if node.kind == "label_definition" {
+            break;
+        }
+        let (pops, pushes) = stack_effect(node.kind);
+        lowest = lowest.min(depth - i64::from(pops));
+        depth += i64::from(pushes) - i64::from(pops);
+        match node.kind {
+            "hop" => break,
+            "leap" => exits.push(depth),
+            _ => {}
+        }
+    }
+    // Jumping away with HOP, falling into the next label or ending the program
+    exits.push(depth);
+
+    BlockEffect {
+        consumes: (-lowest) as u32,
+        net: exits.iter().all(|&d| d == exits[0]).then_some(exits[0]),
+    }
+}
@@ -1,45 +1,849 @@
-use std::{collections::HashMap, sync::Arc};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
 use tokio::sync::RwLock;
-use tower_lsp::jsonrpc::Result;
+use tokio::task::JoinHandle;
+use tower::Service;
+use tower_lsp::jsonrpc::{Request, Response, Result};
+use tower_lsp::lsp_types::notification::LogTrace;
 use tower_lsp::lsp_types::*;
-use tower_lsp::{Client, LanguageServer};
+use tower_lsp::{Client, ClientSocket, ExitedError, LanguageServer, LspService};
 
-use crate::diagnostics::collect_diagnostics;
-use crate::document::{ByteRange, Doc, make_parser};
-use crate::semantic_tokens::{build_semantic_tokens, encode_semantic_tokens, legend};
-use crate::utils::froggy_helpers::{
-    find_label_definition, find_label_references, leading_word_range, make_hover,
+use crate::code_actions::{SnippetCodeAction, quick_fixes, snippet_quick_fixes};
+use crate::completion::completions_at;
+use crate::diagnostics::{INSERT_KEY, internal_error_diag};
+use crate::document::{Doc, ParseError};
+use crate::hover::hover_at;
+use crate::lint::LintConfig;
+use crate::logging::{LogLevel, Logger, target};
+use crate::markdown::MarkdownDoc;
+use crate::notebook::{
+    Cell, DidChangeNotebookDocumentParams, DidCloseNotebookDocumentParams,
+    DidOpenNotebookDocumentParams, Notebook, apply_change, open_cells, owner,
 };
+use crate::project::{CONFIG_FILE, Discovery, ProjectStore};
+use crate::selection_range::selection_range_at;
+use crate::semantic_tokens::{build_semantic_tokens, encode_semantic_tokens, legend};
+use crate::settings::{Settings, SettingsStore};
+use crate::store::DocStore;
+use crate::utils::froggy_helpers::{find_label_definition, find_label_references, label_names};
 use crate::utils::tree_sitter_helpers::{find_node_at_position, labeldef_to_range};
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Backend {
     pub client: Client,
-    pub docs: Arc<RwLock<HashMap<Url, Doc>>>,
+    pub docs: Arc<DocStore>,
+    // Froggy files the client has open, including ones whose first parse failed
+    open: Arc<RwLock<HashSet<Url>>>,
+    // The same for Markdown documents
+    open_markdown: Arc<RwLock<HashSet<Url>>>,
+    pub settings: Arc<RwLock<SettingsStore>>,
+    pub projects: Arc<RwLock<ProjectStore>>,
+    pub logger: Arc<Logger>,
+    // Cancellation flag of the latest parse per document
+    parse_cancel: Arc<Mutex<HashMap<Url, Arc<AtomicBool>>>>,
+    // Pending diagnostics run per document; replaced (and aborted) by each newer edit,
+    // and removed once it has run
+    diagnostics_tasks: Arc<Mutex<HashMap<Url, JoinHandle<()>>>>,
+    // Markdown documents, analysed only inside their fenced Froggy blocks
+    pub markdown: Arc<RwLock<HashMap<Url, MarkdownDoc>>>,
+    // Notebooks by notebook URI; requests arrive with the URIs of their cells
+    pub notebooks: Arc<RwLock<HashMap<Url, Notebook>>>,
+    // Cells of every open notebook as last synced, including ones that didn't parse
+    open_notebooks: Arc<RwLock<HashMap<Url, Vec<Cell>>>>,
+    save_diagnostics: Arc<RwLock<HashMap<Url, SaveDiagnostics>>>,
+    // Whether the client can register notebook sync dynamically
+    can_register_notebooks: Arc<AtomicBool>,
+    // Whether the client applies snippet edits in code actions
+    can_edit_snippets: Arc<AtomicBool>,
+    // Whether the client re-requests semantic tokens when asked to
+    can_refresh_tokens: Arc<AtomicBool>,
+}
+
+// Diagnostics of the checks run on save, and the document version they were computed for
+#[derive(Debug)]
+struct SaveDiagnostics {
+    version: i32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// The server's LspService. `initialize` passes through here first, for the client
+// capabilities lsp-types 0.94 drops while deserializing, and code actions go to a
+// route of their own when they may contain snippet edits.
+pub struct BackendService {
+    inner: LspService<Backend>,
+}
+
+const SNIPPET_CODE_ACTION: &str = "froggy/snippetCodeAction";
+
+impl Service<Request> for BackendService {
+    type Response = Option<Response>;
+    type Error = ExitedError;
+    type Future = <LspService<Backend> as Service<Request>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), ExitedError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let backend = self.inner.inner();
+        match req.method() {
+            "initialize" => {
+                let capability = |pointer: &str| {
+                    req.params()
+                        .and_then(|p| p.pointer(pointer))
+                        .and_then(serde_json::Value::as_bool)
+                        .unwrap_or(false)
+                };
+                backend.can_register_notebooks.store(
+                    capability(
+                        "/capabilities/notebookDocument/synchronization/dynamicRegistration",
+                    ),
+                    Ordering::Relaxed,
+                );
+                backend.can_edit_snippets.store(
+                    capability("/capabilities/workspace/workspaceEdit/snippetEditSupport"),
+                    Ordering::Relaxed,
+                );
+            }
+            "textDocument/codeAction" if backend.can_edit_snippets.load(Ordering::Relaxed) => {
+                let (_, id, params) = req.into_parts();
+                let mut req = Request::build(SNIPPET_CODE_ACTION);
+                if let Some(id) = id {
+                    req = req.id(id);
+                }
+                if let Some(params) = params {
+                    req = req.params(params);
+                }
+                return self.inner.call(req.finish());
+            }
+            _ => {}
+        }
+        self.inner.call(req)
+    }
 }
 
 impl Backend {
+    // The service with the notifications tower-lsp doesn't route itself
+    pub fn service(logger: Logger) -> (BackendService, ClientSocket) {
+        let (inner, socket) = LspService::build(|client| Self::with_logger(client, logger))
+            .custom_method("$/setTrace", Self::set_trace)
+            .custom_method("notebookDocument/didOpen", Self::did_open_notebook)
+            .custom_method("notebookDocument/didChange", Self::did_change_notebook)
+            .custom_method("notebookDocument/didClose", Self::did_close_notebook)
+            .custom_method(SNIPPET_CODE_ACTION, Self::snippet_code_action)
+            .finish();
+        (BackendService { inner }, socket)
+    }
+
     pub fn new(client: Client) -> Self {
+        Self::with_logger(client, Logger::default())
+    }
+
+    pub fn with_logger(client: Client, logger: Logger) -> Self {
         Self {
             client,
-            docs: Arc::new(RwLock::new(HashMap::new())),
+            logger: Arc::new(logger),
+            docs: Arc::new(DocStore::default()),
+            open: Arc::new(RwLock::new(HashSet::new())),
+            open_markdown: Arc::new(RwLock::new(HashSet::new())),
+            settings: Arc::new(RwLock::new(SettingsStore::default())),
+            projects: Arc::new(RwLock::new(ProjectStore::default())),
+            parse_cancel: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics_tasks: Arc::new(Mutex::new(HashMap::new())),
+            markdown: Arc::new(RwLock::new(HashMap::new())),
+            notebooks: Arc::new(RwLock::new(HashMap::new())),
+            open_notebooks: Arc::new(RwLock::new(HashMap::new())),
+            save_diagnostics: Arc::new(RwLock::new(HashMap::new())),
+            can_register_notebooks: Arc::new(AtomicBool::new(false)),
+            can_edit_snippets: Arc::new(AtomicBool::new(false)),
+            can_refresh_tokens: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Parse on a blocking thread so a slow parse can't stall other requests.
+    // Starting a parse cancels any earlier one still running for the same document.
+    async fn parse_in_background<T, F>(
+        &self,
+        uri: &Url,
+        parse: F,
+    ) -> std::result::Result<T, ParseError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&AtomicBool) -> std::result::Result<T, ParseError> + Send + 'static,
+    {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let previous = self
+            .parse_cancel
+            .lock()
+            .unwrap()
+            .insert(uri.clone(), cancel.clone());
+        if let Some(previous) = previous {
+            previous.store(true, Ordering::Relaxed);
+        }
+
+        tokio::task::spawn_blocking(move || parse(&cancel))
+            .await
+            .unwrap_or(Err(ParseError::Panicked))
+    }
+
+    fn cancel_parse(&self, uri: &Url) {
+        if let Some(cancel) = self.parse_cancel.lock().unwrap().remove(uri) {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Surface a failed parse; the document keeps its last good tree, if any
+    async fn report_parse_failure(&self, uri: Url, version: i32, err: ParseError) {
+        if let ParseError::Cancelled = err {
+            self.log(
+                LogLevel::Debug,
+                target::DOCUMENTS,
+                format!("{uri} v{version}: {err}"),
+            )
+            .await;
+            return;
+        }
+
+        self.log(
+            LogLevel::Error,
+            target::DOCUMENTS,
+            format!("Failed to parse {uri} v{version}: {err}"),
+        )
+        .await;
+        // Don't let diagnostics of the last good version replace the error
+        self.cancel_diagnostics(&uri);
+        let diag = internal_error_diag(format!(
+            "Internal error: {err}. Editor features use the last successfully parsed version, if any, until the next edit parses"
+        ));
+        self.client
+            .publish_diagnostics(uri, vec![diag], Some(version))
+            .await;
+    }
+
+    // Write a record to the log file and window/logMessage if its target's level allows it
+    async fn log(&self, level: LogLevel, target: &str, message: impl Into<String>) {
+        let enabled = {
+            let settings = self.settings.read().await;
+            self.logger
+                .filter(&settings.global.log_level)
+                .enabled(target, level)
+        };
+        if !enabled {
+            return;
+        }
+
+        let message = message.into();
+        self.logger.write(level, target, &message);
+        self.client
+            .log_message(level.message_type(), format!("[{target}] {message}"))
+            .await;
+    }
+
+    // Verbose detail, sent as $/logTrace only while the client has tracing on.
+    // `verbose` is only built for `verbose` tracing.
+    async fn trace(&self, target: &str, message: String, verbose: impl FnOnce() -> String) {
+        let trace = self.logger.trace();
+        let to_file = {
+            let settings = self.settings.read().await;
+            self.logger
+                .filter(&settings.global.log_level)
+                .enabled(target, LogLevel::Trace)
+        };
+        if to_file {
+            self.logger.write(LogLevel::Trace, target, &message);
+        }
+        if trace == TraceValue::Off {
+            return;
+        }
+
+        let verbose = (trace == TraceValue::Verbose)
+            .then(verbose)
+            .filter(|v| !v.is_empty());
+        self.client
+            .send_notification::<LogTrace>(LogTraceParams { message, verbose })
+            .await;
+    }
+
+    async fn trace_diagnostics(&self, event: String, diags: &[Diagnostic]) {
+        let message = format!("diagnostics for {event}: {}", diags.len());
+        self.trace(target::DIAGNOSTICS, message, || {
+            diags
+                .iter()
+                .map(|d| {
+                    let start = d.range.start;
+                    format!("{}:{}: {}", start.line + 1, start.character + 1, d.message)
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .await;
+    }
+
+    // Diagnostics of a notebook's program, split up between its cells
+    async fn publish_notebook_diagnostics(&self, uri: Url) {
+        let lints = self.lint_config_for(&uri).await;
+        let computed = {
+            let notebooks = self.notebooks.read().await;
+            notebooks.get(&uri).map(|nb| {
+                let mut by_cell: HashMap<Url, Vec<Diagnostic>> = nb
+                    .cells
+                    .iter()
+                    .map(|c| (c.uri.clone(), Vec::new()))
+                    .collect();
+                for mut diag in nb.doc.diagnostics(&lints) {
+                    if let Some((span, range)) = nb.to_cell(diag.range) {
+                        diag.range = range;
+                        // Where a quick fix inserts a missing operand
+                        if let Some(position) = diag
+                            .data
+                            .as_mut()
+                            .and_then(|d| d.get_mut(INSERT_KEY))
+                            .and_then(|i| i.get_mut("position"))
+                            && let Ok(at) = serde_json::from_value::<Position>(position.clone())
+                            && let Some((_, at)) = nb.to_cell(Range::new(at, at))
+                        {
+                            *position = serde_json::json!(at.start);
+                        }
+                        by_cell.entry(span.uri.clone()).or_default().push(diag);
+                    }
+                }
+                let versions: HashMap<Url, i32> = nb
+                    .cells
+                    .iter()
+                    .map(|c| (c.uri.clone(), c.version))
+                    .collect();
+                (by_cell, versions, nb.version)
+            })
+        };
+        let Some((by_cell, versions, version)) = computed else {
+            return;
+        };
+        if self.current_version(&uri).await != Some(version) {
+            return;
+        }
+
+        for (cell, diags) in by_cell {
+            self.trace_diagnostics(format!("{cell} (notebook {uri} v{version})"), &diags)
+                .await;
+            let version = versions.get(&cell).copied();
+            self.client.publish_diagnostics(cell, diags, version).await;
+        }
+    }
+
+    // Parse a notebook's cells as one program and schedule its diagnostics
+    async fn update_notebook(&self, uri: Url, cells: Vec<Cell>, version: i32, delay: Duration) {
+        let parsed = self
+            .parse_in_background(&uri, move |cancel| Notebook::parse(cells, version, cancel))
+            .await;
+        let nb = match parsed {
+            Ok(nb) => nb,
+            Err(e) => return self.report_parse_failure(uri, version, e).await,
+        };
+
+        {
+            let mut notebooks = self.notebooks.write().await;
+            if notebooks.get(&uri).is_some_and(|n| n.version > version) {
+                return;
+            }
+            notebooks.insert(uri.clone(), nb);
+        }
+        self.schedule_diagnostics(uri, delay).await;
+    }
+
+    async fn did_open_notebook(&self, params: DidOpenNotebookDocumentParams) {
+        let uri = params.notebook_document.uri.clone();
+        let version = params.notebook_document.version;
+        let cells = open_cells(params);
+
+        self.trace(
+            target::DOCUMENTS,
+            format!(
+                "notebookDocument/didOpen: {uri} v{version} cells={}",
+                cells.len()
+            ),
+            String::new,
+        )
+        .await;
+        self.open_notebooks
+            .write()
+            .await
+            .insert(uri.clone(), cells.clone());
+        self.update_notebook(uri, cells, version, Duration::ZERO)
+            .await;
+    }
+
+    async fn did_change_notebook(&self, params: DidChangeNotebookDocumentParams) {
+        let uri = params.notebook_document.uri;
+        let version = params.notebook_document.version;
+
+        // Applied to the cells as synced, not as last parsed, so a change made while the
+        // first parse runs (or after it failed) isn't lost
+        let (cells, removed) = {
+            let mut open = self.open_notebooks.write().await;
+            let Some(cells) = open.get_mut(&uri) else {
+                return;
+            };
+            let removed = apply_change(cells, params.change);
+            (cells.clone(), removed)
+        };
+
+        self.trace(
+            target::DOCUMENTS,
+            format!(
+                "notebookDocument/didChange: {uri} v{version} removed={}",
+                removed.len()
+            ),
+            String::new,
+        )
+        .await;
+        for cell in removed {
+            self.client
+                .publish_diagnostics(cell, Vec::new(), None)
+                .await;
+        }
+
+        let delay = self.diagnostic_delay(&uri).await;
+        self.update_notebook(uri, cells, version, delay).await;
+    }
+
+    async fn did_close_notebook(&self, params: DidCloseNotebookDocumentParams) {
+        let uri = params.notebook_document.uri;
+        self.cancel_parse(&uri);
+        self.cancel_diagnostics(&uri);
+        self.notebooks.write().await.remove(&uri);
+        let Some(cells) = self.open_notebooks.write().await.remove(&uri) else {
+            return;
+        };
+        for cell in cells {
+            self.client
+                .publish_diagnostics(cell.uri, Vec::new(), None)
+                .await;
+        }
+    }
+
+    async fn set_trace(&self, params: SetTraceParams) {
+        self.logger.set_trace(params.value);
+    }
+
+    // Analyse a closed workspace file as saved on disk. Returns whether it could be read
+    // and parsed, or was reopened meanwhile.
+    async fn load_saved(&self, uri: &Url, version: i32) -> bool {
+        let Some(text) = uri
+            .to_file_path()
+            .ok()
+            .and_then(|p| fs::read_to_string(p).ok())
+        else {
+            return false;
+        };
+        // What's on disk is saved, so it gets the save-time checks too
+        let parsed = self
+            .parse_in_background(uri, move |cancel| {
+                let doc = Doc::parse(text, version, cancel)?;
+                let diagnostics = doc.compile_diagnostics();
+                Ok((doc, diagnostics))
+            })
+            .await;
+        let (doc, diagnostics) = match parsed {
+            Ok(parsed) => parsed,
+            Err(ParseError::Cancelled) => return true,
+            Err(_) => return false,
+        };
+        {
+            let open = self.open.read().await;
+            // Reopened while we were parsing
+            if open.contains(uri) {
+                return true;
+            }
+            self.docs.insert(uri.clone(), doc);
+            let saved = SaveDiagnostics {
+                version,
+                diagnostics,
+            };
+            self.save_diagnostics
+                .write()
+                .await
+                .insert(uri.clone(), saved);
+        }
+        self.schedule_diagnostics(uri.clone(), Duration::ZERO).await;
+        true
+    }
+
+    // A watched `.frog` file changed on disk; only closed files kept for the workspace
+    // are affected, as open ones follow the editor's buffer
+    async fn refresh_saved(&self, change: FileEvent) {
+        let uri = change.uri;
+        if self.open.read().await.contains(&uri) {
+            return;
+        }
+        let Some(version) = self.docs.version(&uri) else {
+            return;
+        };
+        if change.typ != FileChangeType::DELETED && self.load_saved(&uri, version).await {
+            return;
+        }
+        let removed = {
+            let open = self.open.read().await;
+            !open.contains(&uri) && self.docs.remove(&uri).is_some()
+        };
+        if removed {
+            self.cancel_diagnostics(&uri);
+            self.save_diagnostics.write().await.remove(&uri);
+            self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        }
+    }
+
+    // `textDocument/codeAction` from clients with `snippetEditSupport`
+    async fn snippet_code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> Result<Option<Vec<SnippetCodeAction>>> {
+        let uri = &params.text_document.uri;
+        let labels = self.labels_at(uri, params.range.start).await;
+        let actions = snippet_quick_fixes(uri, &params.context.diagnostics, &labels);
+        Ok((!actions.is_empty()).then_some(actions))
+    }
+
+    // Parse a settings object, logging problems; None if it couldn't be used at all
+    async fn parse_settings(&self, source: &str, value: serde_json::Value) -> Option<Settings> {
+        let log_level = self.settings.read().await.global.log_level.clone();
+        match Settings::from_json(value, &log_level) {
+            Ok((settings, problems)) => {
+                for problem in problems {
+                    self.log(
+                        LogLevel::Warning,
+                        target::CONFIG,
+                        format!("{source}: {problem}"),
+                    )
+                    .await;
+                }
+                Some(settings)
+            }
+            Err(e) => {
+                self.log(LogLevel::Error, target::CONFIG, format!("{source}: {e}"))
+                    .await;
+                None
+            }
+        }
+    }
+
+    // Ask the client for the `froggy` section, globally and for each workspace folder
+    async fn pull_configuration(&self) {
+        let folders: Vec<Url> = {
+            let store = self.settings.read().await;
+            if !store.can_pull {
+                return;
+            }
+            store.folders.iter().map(|(uri, _)| uri.clone()).collect()
+        };
+
+        let items = std::iter::once(None)
+            .chain(folders.iter().cloned().map(Some))
+            .map(|scope_uri| ConfigurationItem {
+                scope_uri,
+                section: Some("froggy".to_string()),
+            })
+            .collect();
+
+        let values = match self.client.configuration(items).await {
+            Ok(values) => values,
+            Err(e) => {
+                self.log(
+                    LogLevel::Warning,
+                    target::CONFIG,
+                    format!("workspace/configuration failed: {e}"),
+                )
+                .await;
+                return;
+            }
+        };
+
+        let mut values = values.into_iter();
+        let global = match values.next() {
+            Some(v) if !v.is_null() => self.parse_settings("workspace/configuration", v).await,
+            _ => None,
+        };
+
+        let mut scoped = Vec::new();
+        for (folder, value) in folders.into_iter().zip(values) {
+            if !value.is_null()
+                && let Some(s) = self.parse_settings("workspace/configuration", value).await
+            {
+                scoped.push((folder, s));
+            }
+        }
+
+        let mut store = self.settings.write().await;
+        if let Some(global) = global {
+            store.global = global;
+        }
+        let fallback = store.global.clone();
+        for (folder, settings) in store.folders.iter_mut() {
+            *settings = scoped
+                .iter()
+                .find(|(f, _)| f == folder)
+                .map_or_else(|| fallback.clone(), |(_, s)| s.clone());
+        }
+    }
+
+    // Client lint settings for `uri` with its project's froggy.toml layered on top
+    async fn lint_config_for(&self, uri: &Url) -> LintConfig {
+        let client_lints = self.settings.read().await.for_uri(uri).lints.clone();
+
+        let cached = {
+            let projects = self.projects.read().await;
+            projects.cached(uri).map(|c| c.map(|c| c.lints.clone()))
+        };
+        let (project_lints, loaded) = match cached {
+            Some(lints) => (lints, None),
+            None => {
+                // Reading the file system can block; do it without holding the lock
+                let found = {
+                    let uri = uri.clone();
+                    tokio::task::spawn_blocking(move || Discovery::run(&uri))
+                        .await
+                        .unwrap_or_default()
+                };
+                let mut projects = self.projects.write().await;
+                let (config, loaded) = projects.insert(uri, found);
+                (config.map(|c| c.lints.clone()), loaded)
+            }
+        };
+
+        // A config file read for the first time gets its own diagnostics
+        if let Some((config_uri, diags)) = loaded {
+            self.client
+                .publish_diagnostics(config_uri, diags, None)
+                .await;
+        }
+
+        match project_lints {
+            Some(project) => client_lints.merged(&project),
+            None => client_lints,
+        }
+    }
+
+    // Recompute and publish diagnostics for every open document
+    async fn republish_diagnostics(&self) {
+        let mut uris = self.docs.uris();
+        uris.extend(self.markdown.read().await.keys().cloned());
+        uris.extend(self.notebooks.read().await.keys().cloned());
+        for uri in uris {
+            self.schedule_diagnostics(uri, Duration::ZERO).await;
+        }
+    }
+
+    // Recompute diagnostics for `uri` once no newer edit has arrived for `delay`, and
+    // have the client refresh its semantic tokens. Scheduling again before then cancels
+    // the earlier run.
+    async fn schedule_diagnostics(&self, uri: Url, delay: Duration) {
+        let this = self.clone();
+        let key = uri.clone();
+        // Held until the task is stored, so a task that finishes at once still finds it
+        let mut tasks = self.diagnostics_tasks.lock().unwrap();
+        let task = tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            this.publish_diagnostics_for(uri.clone()).await;
+            if this.can_refresh_tokens.load(Ordering::Relaxed) {
+                let _ = this.client.semantic_tokens_refresh().await;
+            }
+            // Unless a newer run has taken its place
+            let mut tasks = this.diagnostics_tasks.lock().unwrap();
+            if tasks.get(&uri).is_some_and(|t| t.id() == tokio::task::id()) {
+                tasks.remove(&uri);
+            }
+        });
+        if let Some(stale) = tasks.insert(key, task) {
+            stale.abort();
+        }
+    }
+
+    fn cancel_diagnostics(&self, uri: &Url) {
+        if let Some(task) = self.diagnostics_tasks.lock().unwrap().remove(uri) {
+            task.abort();
+        }
+    }
+
+    async fn diagnostic_delay(&self, uri: &Url) -> Duration {
+        let ms = self.settings.read().await.for_uri(uri).diagnostic_delay;
+        Duration::from_millis(ms)
+    }
+
+    // Version of the notebook, Markdown or Froggy document stored for `uri`
+    async fn current_version(&self, uri: &Url) -> Option<i32> {
+        if let Some(nb) = self.notebooks.read().await.get(uri) {
+            return Some(nb.version);
+        }
+        match self.markdown.read().await.get(uri) {
+            Some(md) => Some(md.version),
+            None => self.docs.version(uri),
+        }
+    }
+
+    // Parse the fenced Froggy blocks of a Markdown document and schedule its diagnostics
+    async fn update_markdown(&self, uri: Url, text: String, version: i32, delay: Duration) {
+        let parsed = self
+            .parse_in_background(&uri, move |cancel| {
+                MarkdownDoc::parse(&text, version, cancel)
+            })
+            .await;
+        let md = match parsed {
+            Ok(md) => md,
+            Err(e) => return self.report_parse_failure(uri, version, e).await,
+        };
+
+        {
+            let mut markdown = self.markdown.write().await;
+            if markdown.get(&uri).is_some_and(|m| m.version > version) {
+                return;
+            }
+            markdown.insert(uri.clone(), md);
+        }
+        self.schedule_diagnostics(uri, delay).await;
+    }
+
+    // Labels of the program `position` in `uri` belongs to, in source order
+    async fn labels_at(&self, uri: &Url, position: Position) -> Vec<String> {
+        let names = |doc: &Doc| -> Vec<String> {
+            label_names(doc.index())
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        };
+        if let Some(md) = self.markdown.read().await.get(uri) {
+            return md.block_at(position).map(names).unwrap_or_default();
+        }
+        if let Some(nb) = owner(&*self.notebooks.read().await, uri) {
+            return names(&nb.doc);
+        }
+        self.docs
+            .get(uri)
+            .map(|doc| names(&doc))
+            .unwrap_or_default()
+    }
+
+    // Publish diagnostics for the current version of `uri`, tagged with that version
+    async fn publish_diagnostics_for(&self, uri: Url) {
+        if self.notebooks.read().await.contains_key(&uri) {
+            return self.publish_notebook_diagnostics(uri).await;
+        }
+
+        let lints = self.lint_config_for(&uri).await;
+        let computed = match self.markdown.read().await.get(&uri) {
+            Some(md) => {
+                let diags = md
+                    .blocks
+                    .iter()
+                    .flat_map(|b| b.doc.diagnostics(&lints))
+                    .collect();
+                Some((diags, md.version))
+            }
+            None => {
+                let saved = self.save_diagnostics.read().await;
+                self.docs.get(&uri).map(|doc| {
+                    let mut diags = doc.diagnostics(&lints);
+                    // Save-time checks only hold until the next edit
+                    if let Some(saved) = saved.get(&uri)
+                        && saved.version == doc.version
+                    {
+                        diags.extend(saved.diagnostics.iter().cloned());
+                    }
+                    (diags, doc.version)
+                })
+            }
+        };
+        let Some((diags, version)) = computed else {
+            return;
+        };
+
+        // The document moved on while we were computing; its own run will publish
+        if self.current_version(&uri).await != Some(version) {
+            return;
         }
+
+        self.trace_diagnostics(format!("{uri} v{version}"), &diags)
+            .await;
+        self.client
+            .publish_diagnostics(uri, diags, Some(version))
+            .await;
     }
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        let global = match params.initialization_options {
+            Some(opts) => self
+                .parse_settings("initializationOptions", opts)
+                .await
+                .unwrap_or_default(),
+            None => Settings::default(),
+        };
+
+        {
+            let mut store = self.settings.write().await;
+            store.can_pull = params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|ws| ws.configuration)
+                .unwrap_or(false);
+            self.can_refresh_tokens.store(
+                params
+                    .capabilities
+                    .workspace
+                    .as_ref()
+                    .and_then(|ws| ws.semantic_tokens.as_ref())
+                    .and_then(|t| t.refresh_support)
+                    .unwrap_or(false),
+                Ordering::Relaxed,
+            );
+            self.projects.write().await.can_watch = params
+                .capabilities
+                .workspace
+                .as_ref()
+                .and_then(|ws| ws.did_change_watched_files.as_ref())
+                .and_then(|w| w.dynamic_registration)
+                .unwrap_or(false);
+            store.folders = params
+                .workspace_folders
+                .unwrap_or_default()
+                .into_iter()
+                .map(|folder| (folder.uri, global.clone()))
+                .collect();
+            store.global = global;
+        }
+        if let Some(trace) = params.trace {
+            self.logger.set_trace(trace);
+        }
+
         Ok(InitializeResult {
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::FULL),
+                        save: Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+                            include_text: Some(true),
+                        })),
+                        ..Default::default()
+                    },
                 )),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 completion_provider: Some(CompletionOptions::default()),
                 definition_provider: Some(OneOf::Left(true)),
                 references_provider: Some(OneOf::Left(true)),
                 document_symbol_provider: Some(OneOf::Left(true)),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                code_action_provider: Some(CodeActionProviderCapability::Options(
+                    CodeActionOptions {
+                        code_action_kinds: Some(vec![CodeActionKind::QUICKFIX]),
+                        ..Default::default()
+                    },
+                )),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensOptions(
                         SemanticTokensOptions {
@@ -58,9 +862,108 @@ impl LanguageServer for Backend {
     }
 
     async fn initialized(&self, _: InitializedParams) {
-        self.client
-            .log_message(MessageType::INFO, "server initialised!")
+        self.log(LogLevel::Info, target::SERVER, "server initialised!")
             .await;
+
+        if self.projects.read().await.can_watch {
+            let watch = DidChangeWatchedFilesRegistrationOptions {
+                watchers: vec![
+                    FileSystemWatcher {
+                        glob_pattern: GlobPattern::String(format!("**/{CONFIG_FILE}")),
+                        kind: None,
+                    },
+                    // Closed workspace files are analysed as saved
+                    FileSystemWatcher {
+                        glob_pattern: GlobPattern::String("**/*.frog".to_string()),
+                        kind: None,
+                    },
+                ],
+            };
+            let registration = Registration {
+                id: "froggy-file-watcher".to_string(),
+                method: "workspace/didChangeWatchedFiles".to_string(),
+                register_options: serde_json::to_value(watch).ok(),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                self.log(
+                    LogLevel::Warning,
+                    target::CONFIG,
+                    format!("Could not watch {CONFIG_FILE} and .frog files: {e}"),
+                )
+                .await;
+            }
+        }
+
+        // lsp-types has no notebook capabilities yet, so ask for Froggy cells dynamically
+        if self.can_register_notebooks.load(Ordering::Relaxed) {
+            let registration = Registration {
+                id: "froggy-notebook-sync".to_string(),
+                method: "notebookDocument/sync".to_string(),
+                register_options: Some(serde_json::json!({
+                    "notebookSelector": [{ "cells": [{ "language": "froggy" }] }]
+                })),
+            };
+            if let Err(e) = self.client.register_capability(vec![registration]).await {
+                self.log(
+                    LogLevel::Warning,
+                    target::SERVER,
+                    format!("Could not register notebook sync: {e}"),
+                )
+                .await;
+            }
+        }
+
+        // Settings pulled here may differ from initializationOptions
+        self.pull_configuration().await;
+        self.republish_diagnostics().await;
+    }
+
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        // Push-style clients send the settings; pull-style clients send null and expect a request
+        let section = match params.settings {
+            serde_json::Value::Object(mut map) if map.contains_key("froggy") => {
+                map.remove("froggy").unwrap_or_default()
+            }
+            other => other,
+        };
+
+        if !section.is_null()
+            && let Some(settings) = self.parse_settings("didChangeConfiguration", section).await
+        {
+            let mut store = self.settings.write().await;
+            for (_, scoped) in store.folders.iter_mut() {
+                *scoped = settings.clone();
+            }
+            store.global = settings;
+        }
+
+        self.pull_configuration().await;
+        self.republish_diagnostics().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let (changed, programs): (Vec<FileEvent>, Vec<FileEvent>) =
+            params.changes.into_iter().partition(|c| {
+                c.uri.path_segments().and_then(|mut s| s.next_back()) == Some(CONFIG_FILE)
+            });
+        for change in programs {
+            self.refresh_saved(change).await;
+        }
+        if changed.is_empty() {
+            return;
+        }
+
+        // Creating or deleting a config can change which one governs a document
+        self.projects.write().await.invalidate();
+        for change in changed {
+            if change.typ == FileChangeType::DELETED {
+                self.client
+                    .publish_diagnostics(change.uri, Vec::new(), None)
+                    .await;
+            }
+        }
+
+        self.republish_diagnostics().await;
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
@@ -68,33 +971,43 @@ impl LanguageServer for Backend {
         let text = params.text_document.text;
         let version = params.text_document.version;
 
-        let mut parser = make_parser();
-        let tree = parser.parse(&text, None).expect("parse() returned None");
-
-        let doc = Doc::new(text, version, tree);
-
-        let diags = collect_diagnostics(&doc.tree, &doc);
+        if params.text_document.language_id == "markdown" {
+            // Before parsing, like Froggy files below
+            self.open_markdown.write().await.insert(uri.clone());
+            return self
+                .update_markdown(uri, text, version, Duration::ZERO)
+                .await;
+        }
 
-        self.client
-            .log_message(
-                MessageType::INFO,
-                format!("didOpen: Found {} diagnostics", diags.len()),
-            )
-            .await;
+        // Before parsing, so that edits made while this parse runs (or after it failed)
+        // aren't dropped. A stored copy is the saved file of a closed workspace document,
+        // and its checks don't apply to the editor's buffer.
+        {
+            let mut open = self.open.write().await;
+            open.insert(uri.clone());
+            self.docs.remove(&uri);
+            self.save_diagnostics.write().await.remove(&uri);
+        }
+        let doc = match self
+            .parse_in_background(&uri, move |cancel| Doc::parse(text, version, cancel))
+            .await
+        {
+            Ok(doc) => doc,
+            Err(e) => return self.report_parse_failure(uri, version, e).await,
+        };
 
-        self.client
-            .publish_diagnostics(uri.clone(), diags, None)
-            .await;
+        self.trace(
+            target::DOCUMENTS,
+            format!("didOpen: {uri} v{version} len={}", doc.text.len()),
+            String::new,
+        )
+        .await;
 
-        self.client
-            .log_message(
-                MessageType::INFO,
-                format!("didOpen: {uri} v{version} len={}", doc.text.len()),
-            )
-            .await;
-
-        self.docs.write().await.insert(uri, doc);
-        let _ = self.client.semantic_tokens_refresh().await;
+        // An edit made while this parse ran is newer
+        if !self.docs.update(uri.clone(), doc) {
+            return;
+        }
+        self.schedule_diagnostics(uri, Duration::ZERO).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -102,205 +1015,153 @@ impl LanguageServer for Backend {
         let version = params.text_document.version;
         let change_count = params.content_changes.len();
 
-        let mut doc = {
-            let mut docs = self.docs.write().await;
-            match docs.remove(&uri) {
-                Some(d) => d,
-                None => return,
-            }
+        let is_markdown = self.open_markdown.read().await.contains(&uri);
+        if !is_markdown && !self.open.read().await.contains(&uri) {
+            return;
+        }
+
+        // Full sync: the last change holds the whole document
+        let Some(change) = params.content_changes.into_iter().last() else {
+            return;
         };
+        let text = change.text;
+        if is_markdown {
+            let delay = self.diagnostic_delay(&uri).await;
+            return self.update_markdown(uri, text, version, delay).await;
+        }
+
+        let doc = match self
+            .parse_in_background(&uri, move |cancel| Doc::parse(text, version, cancel))
+            .await
+        {
+            Ok(doc) => doc,
+            Err(e) => return self.report_parse_failure(uri, version, e).await,
+        };
+
+        self.trace(
+            target::DOCUMENTS,
+            format!("didChange: {uri} v{version} changes={change_count}"),
+            String::new,
+        )
+        .await;
 
-        for change in params.content_changes {
-            let mut parser = make_parser();
-            doc.update(change.text, version, &mut parser);
+        // A slower parse of an older version mustn't overwrite a newer one
+        if !self.docs.update(uri.clone(), doc) {
+            return;
         }
+        let delay = self.diagnostic_delay(&uri).await;
+        self.schedule_diagnostics(uri, delay).await;
+    }
 
-        let diags = collect_diagnostics(&doc.tree, &doc);
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.trace(target::DOCUMENTS, format!("didSave: {uri}"), String::new)
+            .await;
 
-        let log_msg = format!(
-            "didChange: {uri} v{version} changes={change_count}, diagnostics={}",
-            diags.len()
+        let Some(doc) = self.docs.get(&uri) else {
+            return;
+        };
+        // The checks run on the text as saved. If the buffer no longer holds it, an edit
+        // arrived after the save and the results would be stale before they're shown.
+        if params.text.is_some_and(|text| text != doc.text) {
+            return;
+        }
+        let version = doc.version;
+        let Ok(diagnostics) = tokio::task::spawn_blocking(move || doc.compile_diagnostics()).await
+        else {
+            return;
+        };
+        self.save_diagnostics.write().await.insert(
+            uri.clone(),
+            SaveDiagnostics {
+                version,
+                diagnostics,
+            },
         );
+        self.schedule_diagnostics(uri, Duration::ZERO).await;
+    }
 
-        self.client.log_message(MessageType::INFO, log_msg).await;
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.cancel_parse(&uri);
+        self.cancel_diagnostics(&uri);
+        self.trace(target::DOCUMENTS, format!("didClose: {uri}"), String::new)
+            .await;
 
+        if self.open_markdown.write().await.remove(&uri) {
+            self.markdown.write().await.remove(&uri);
+            return self.client.publish_diagnostics(uri, Vec::new(), None).await;
+        }
+        self.open.write().await.remove(&uri);
+        self.save_diagnostics.write().await.remove(&uri);
+        let Some(closed) = self.docs.remove(&uri) else {
+            return;
+        };
+
+        // Workspace files stay analysed as saved on disk, so their problems stay listed,
+        // if the watcher tells us when that changes
+        let watched = self.projects.read().await.can_watch;
+        if watched
+            && self.settings.read().await.in_workspace(&uri)
+            && self.load_saved(&uri, closed.version).await
         {
-            let mut docs = self.docs.write().await;
-            docs.insert(uri.clone(), doc);
+            return;
         }
-        self.client.publish_diagnostics(uri, diags, None).await;
-        let _ = self.client.semantic_tokens_refresh().await;
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
     }
 
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
 
-    async fn completion(&self, _: CompletionParams) -> Result<Option<CompletionResponse>> {
-        Ok(Some(CompletionResponse::Array(vec![
-            CompletionItem::new_simple("Hello".to_string(), "Some detail".to_string()),
-            CompletionItem::new_simple("Bye".to_string(), "More detail".to_string()),
-        ])))
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let uri = &params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let items = if let Some(md) = self.markdown.read().await.get(uri) {
+            md.block_at(position)
+                .map(|doc| completions_at(doc, position))
+                .unwrap_or_default()
+        } else if let Some(nb) = owner(&*self.notebooks.read().await, uri) {
+            // Labels of every cell can be jumped to
+            nb.to_doc(uri, position)
+                .map(|p| completions_at(&nb.doc, p))
+                .unwrap_or_default()
+        } else {
+            match self.docs.get(uri) {
+                Some(doc) => completions_at(&doc, position),
+                None => return Ok(None),
+            }
+        };
+        Ok(Some(CompletionResponse::Array(items)))
     }
 
     async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let docs = self.docs.read().await;
-        let doc = match docs.get(uri) {
+        if let Some(md) = self.markdown.read().await.get(uri) {
+            return Ok(md
+                .block_at(position)
+                .and_then(|doc| hover_at(doc, position)));
+        }
+        if let Some(nb) = owner(&*self.notebooks.read().await, uri) {
+            let hover = nb
+                .to_doc(uri, position)
+                .and_then(|p| hover_at(&nb.doc, p))
+                .map(|mut hover| {
+                    hover.range = hover.range.and_then(|r| nb.to_cell(r)).map(|(_, r)| r);
+                    hover
+                });
+            return Ok(hover);
+        }
+
+        let doc = match self.docs.get(uri) {
             Some(d) => d,
             None => return Ok(None),
         };
 
-        let node = find_node_at_position(&doc.tree, doc, position);
-        let bytes = doc.text.as_bytes();
-
-        let mut cur = node;
-
-        loop {
-            let r = leading_word_range(&doc.text, cur);
-            match cur.kind() {
-                // Stack operations (both the rule names and string literals)
-                "PLOP" | "plop" => {
-                    return Ok(Some(make_hover(
-                        "PLOP <value>: Push a value onto the stack",
-                        r,
-                        doc,
-                    )));
-                }
-                "SPLASH" | "splash" => {
-                    return Ok(Some(make_hover(
-                        "SPLASH: Pop a value off the stack",
-                        r,
-                        doc,
-                    )));
-                }
-                "GULP" | "gulp" => {
-                    return Ok(Some(make_hover("GULP: Increment top of stack", r, doc)));
-                }
-                "BURP" | "burp" => {
-                    return Ok(Some(make_hover("BURP: Decrement top of stack", r, doc)));
-                }
-                "DUP" | "dup" => {
-                    return Ok(Some(make_hover("DUP: Duplicate top of stack", r, doc)));
-                }
-                "SWAP" | "swap" => {
-                    return Ok(Some(make_hover("SWAP: Swap top two stack values", r, doc)));
-                }
-                "OVER" | "over" => {
-                    return Ok(Some(make_hover(
-                        "OVER: Duplicate second from top of stack",
-                        r,
-                        doc,
-                    )));
-                }
-
-                // Control flow (both rule names and string literals)
-                "LILY" | "lily" => {
-                    return Ok(Some(make_hover(
-                        "LILY <label>: Define a lilypad label",
-                        r,
-                        doc,
-                    )));
-                }
-                "HOP" | "hop" => {
-                    return Ok(Some(make_hover(
-                        "HOP <Lilypad>: Unconditional jump to a lilypad",
-                        r,
-                        doc,
-                    )));
-                }
-                "LEAP" | "leap" => {
-                    return Ok(Some(make_hover(
-                        "LEAP <Lilypad>: Pop a, if (a == 0) then jump to lilypad",
-                        r,
-                        doc,
-                    )));
-                }
-
-                // Label definition
-                "label_definition" => {
-                    if let Some(label_node) = cur.child_by_field_name("name") {
-                        let label_text = label_node.utf8_text(bytes).unwrap_or("");
-                        let label_range = ByteRange {
-                            start: label_node.start_byte(),
-                            end: label_node.end_byte(),
-                        };
-                        return Ok(Some(make_hover(
-                            &format!("Label definition: {}", label_text),
-                            label_range,
-                            doc,
-                        )));
-                    }
-                }
-
-                // IO (both rule names and string literals)
-                "RIBBIT" | "ribbit" => {
-                    return Ok(Some(make_hover("RIBBIT: Print top of stack", r, doc)));
-                }
-                "CROAK" | "croak" => return Ok(Some(make_hover("CROAK: Read input", r, doc))),
-
-                // Arithmetic (both rule names and string literals)
-                "ADD" | "add" => return Ok(Some(make_hover("ADD: Pop a b, push (b + a)", r, doc))),
-                "SUB" | "sub" => return Ok(Some(make_hover("SUB: Pop a b, push (b - a)", r, doc))),
-                "MUL" | "mul" => return Ok(Some(make_hover("MUL: Pop a b, push (b * a)", r, doc))),
-                "DIV" | "div" => return Ok(Some(make_hover("DIV: Pop a b, push (b / a)", r, doc))),
-
-                // Comparison (both rule names and string literals)
-                "EQUALS" | "equals" => {
-                    return Ok(Some(make_hover("EQUALS: Pop a b, push (b == a)", r, doc)));
-                }
-                "NOT_EQUAL" | "not_equal" => {
-                    return Ok(Some(make_hover(
-                        "NOT_EQUAL: Pop a b, push (b != a)",
-                        r,
-                        doc,
-                    )));
-                }
-                "LESS_THAN" | "less_than" => {
-                    return Ok(Some(make_hover("LESS_THAN: Pop a b, push (b < a)", r, doc)));
-                }
-                "GREATER_THAN" | "greater_than" => {
-                    return Ok(Some(make_hover(
-                        "GREATER_THAN: Pop a b, push (b > a)",
-                        r,
-                        doc,
-                    )));
-                }
-                "LESS_EQ" | "less_eq" => {
-                    return Ok(Some(make_hover("LESS_EQ: Pop a b, push (b <= a)", r, doc)));
-                }
-                "GREATER_EQ" | "greater_eq" => {
-                    return Ok(Some(make_hover(
-                        "GREATER_EQ: Pop a b, push (b >= a)",
-                        r,
-                        doc,
-                    )));
-                }
-                "identifier" => {
-                    if let Some(parent) = cur.parent() {
-                        if matches!(parent.kind(), "label_definition" | "hop" | "leap") {
-                            // Continue to parent instead of returning
-                        } else {
-                            let text = cur.utf8_text(bytes).unwrap_or("");
-                            if let Some(_def) = find_label_definition(&doc.index, text) {
-                                return Ok(Some(make_hover(&format!("Label: {}", text), r, doc)));
-                            }
-                        }
-                    }
-                }
-
-                _ => {}
-            }
-            // Break out of loop if node is parentless
-            match cur.parent() {
-                Some(p) => cur = p,
-                None => break,
-            }
-        }
-
-        Ok(None)
+        Ok(hover_at(&doc, position))
     }
 
     async fn goto_definition(
@@ -310,30 +1171,41 @@ impl LanguageServer for Backend {
         let uri = &params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let docs = self.docs.read().await;
-        let doc = match docs.get(uri) {
+        if let Some(md) = self.markdown.read().await.get(uri) {
+            let range = md
+                .block_at(position)
+                .and_then(|doc| definition_at(doc, position));
+            return Ok(range.map(|range| {
+                GotoDefinitionResponse::Scalar(Location {
+                    uri: uri.clone(),
+                    range,
+                })
+            }));
+        }
+        if let Some(nb) = owner(&*self.notebooks.read().await, uri) {
+            // The label may be defined in another cell
+            let location = nb
+                .to_doc(uri, position)
+                .and_then(|p| definition_at(&nb.doc, p))
+                .and_then(|r| nb.to_cell(r))
+                .map(|(span, range)| Location {
+                    uri: span.uri.clone(),
+                    range,
+                });
+            return Ok(location.map(GotoDefinitionResponse::Scalar));
+        }
+
+        let doc = match self.docs.get(uri) {
             Some(d) => d,
             None => return Ok(None),
         };
 
-        let node = find_node_at_position(&doc.tree, doc, position);
-
-        if node.kind() == "identifier" {
-            if let Some(parent) = node.parent() {
-                if parent.kind() == "hop" || parent.kind() == "leap" {
-                    let label_name = node.utf8_text(doc.text.as_bytes()).unwrap_or("__unknown__");
-
-                    if let Some(def_node) = find_label_definition(&doc.index, label_name) {
-                        return Ok(Some(GotoDefinitionResponse::Scalar(Location {
-                            uri: uri.clone(),
-                            range: labeldef_to_range(def_node, doc),
-                        })));
-                    }
-                }
-            }
-        }
-
-        Ok(None)
+        Ok(definition_at(&doc, position).map(|range| {
+            GotoDefinitionResponse::Scalar(Location {
+                uri: uri.clone(),
+                range,
+            })
+        }))
     }
 
     async fn semantic_tokens_full(
@@ -342,14 +1214,37 @@ impl LanguageServer for Backend {
     ) -> Result<Option<SemanticTokensResult>> {
         let uri = &params.text_document.uri;
 
-        let docs = self.docs.read().await;
-        let doc = match docs.get(uri) {
+        if let Some(md) = self.markdown.read().await.get(uri) {
+            let toks = md
+                .blocks
+                .iter()
+                .flat_map(|b| build_semantic_tokens(&b.doc))
+                .collect();
+            return Ok(Some(SemanticTokensResult::Tokens(encode_semantic_tokens(
+                toks,
+            ))));
+        }
+        if let Some(nb) = owner(&*self.notebooks.read().await, uri) {
+            let Some(lines) = nb.span(uri).map(|s| s.lines.clone()) else {
+                return Ok(None);
+            };
+            let toks = build_semantic_tokens(&nb.doc)
+                .into_iter()
+                .filter(|t| lines.contains(&t.line()))
+                .map(|t| t.shifted_up(lines.start))
+                .collect();
+            return Ok(Some(SemanticTokensResult::Tokens(encode_semantic_tokens(
+                toks,
+            ))));
+        }
+
+        let doc = match self.docs.get(uri) {
             Some(d) => d,
             None => return Ok(None),
         };
 
         Ok(Some(SemanticTokensResult::Tokens(encode_semantic_tokens(
-            build_semantic_tokens(doc),
+            build_semantic_tokens(&doc),
         ))))
     }
 
@@ -357,29 +1252,28 @@ impl LanguageServer for Backend {
         let uri = &params.text_document_position.text_document.uri;
         let position = params.text_document_position.position;
 
-        let docs = self.docs.read().await;
-        let doc = match docs.get(uri) {
+        let doc = match self.docs.get(uri) {
             Some(d) => d,
             None => return Ok(None),
         };
 
-        let node = find_node_at_position(&doc.tree, doc, position);
+        let node = find_node_at_position(&doc.tree, &doc, position);
 
         if node.kind() == "identifier" {
             let label_name = node.utf8_text(doc.text.as_bytes()).unwrap_or("__unknown__");
             let mut locations: Vec<Location> = Vec::new();
 
             // Add def if exists
-            if params.context.include_declaration {
-                if let Some(def) = find_label_definition(&doc.index, label_name) {
-                    locations.push(Location::new(uri.clone(), labeldef_to_range(def, doc)));
-                }
+            if params.context.include_declaration
+                && let Some(def) = find_label_definition(doc.index(), label_name)
+            {
+                locations.push(Location::new(uri.clone(), labeldef_to_range(def, &doc)));
             }
 
             // Add refs
-            if let Some(refs) = find_label_references(&doc.index, label_name) {
+            if let Some(refs) = find_label_references(doc.index(), label_name) {
                 for r in refs {
-                    let ltr = labeldef_to_range(r, doc);
+                    let ltr = labeldef_to_range(r, &doc);
                     locations.push(Location::new(uri.clone(), ltr));
                 }
             }
@@ -392,31 +1286,82 @@ impl LanguageServer for Backend {
     }
 
     async fn document_symbol(
-    &self,
-    params: DocumentSymbolParams,
-) -> Result<Option<DocumentSymbolResponse>> {
-    let uri = &params.text_document.uri;
-    let docs = self.docs.read().await;
-    let doc = match docs.get(uri) {
-        Some(d) => d,
-        None => return Ok(None),
-    };
-
-#[allow(deprecated)]
-let symbols: Vec<DocumentSymbol> = doc.index.label_defs
-    .iter()
-    .map(|(name, range)| DocumentSymbol {
-        name: name.clone(),
-        detail: Some("Label".to_string()),
-        kind: SymbolKind::FUNCTION,
-        range: labeldef_to_range(range, doc),
-        selection_range: labeldef_to_range(range, doc),
-        children: None,
-        tags: None,
-        deprecated: None,
-    })
-    .collect();
-
-    Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let uri = &params.text_document.uri;
+        let doc = match self.docs.get(uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        #[allow(deprecated)]
+        let symbols: Vec<DocumentSymbol> = doc
+            .index()
+            .label_defs
+            .iter()
+            .map(|(name, range)| DocumentSymbol {
+                name: name.clone(),
+                // The first line of the label's doc comment, if it has one
+                detail: Some(
+                    doc.index()
+                        .label_docs
+                        .get(name)
+                        .and_then(|docs| docs.lines().next())
+                        .unwrap_or("Label")
+                        .to_string(),
+                ),
+                kind: SymbolKind::FUNCTION,
+                range: labeldef_to_range(range, &doc),
+                selection_range: labeldef_to_range(range, &doc),
+                children: None,
+                tags: None,
+                deprecated: None,
+            })
+            .collect();
+
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = &params.text_document.uri;
+        let labels = self.labels_at(uri, params.range.start).await;
+        let actions = quick_fixes(uri, &params.context.diagnostics, &labels);
+        Ok((!actions.is_empty()).then_some(actions))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> Result<Option<Vec<SelectionRange>>> {
+        let uri = &params.text_document.uri;
+        let doc = match self.docs.get(uri) {
+            Some(d) => d,
+            None => return Ok(None),
+        };
+
+        Ok(Some(
+            params
+                .positions
+                .into_iter()
+                .map(|p| selection_range_at(&doc, p))
+                .collect(),
+        ))
+    }
 }
+
+// Range of the label definition targeted by the HOP/LEAP operand under `position`
+fn definition_at(doc: &Doc, position: Position) -> Option<Range> {
+    let node = find_node_at_position(&doc.tree, doc, position);
+
+    if node.kind() == "identifier"
+        && let Some(parent) = node.parent()
+        && (parent.kind() == "hop" || parent.kind() == "leap")
+    {
+        let label_name = node.utf8_text(doc.text.as_bytes()).unwrap_or("__unknown__");
+        return find_label_definition(doc.index(), label_name)
+            .map(|def| labeldef_to_range(def, doc));
+    }
+
+    None
 }
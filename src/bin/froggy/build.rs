@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use lsp_froggy::compiler::{CompileError, compile};
+use lsp_froggy::document::Doc;
+
+const USAGE: &str = "Usage: froggy build [-o <out.frogc>] <file.frog>...";
+
+pub fn run(args: Vec<String>) -> i32 {
+    let mut output = None;
+    let mut inputs = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("{USAGE}");
+                    return 2;
+                }
+            },
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if inputs.is_empty() || (output.is_some() && inputs.len() > 1) {
+        eprintln!("{USAGE}");
+        return 2;
+    }
+
+    let mut failed = false;
+    for input in &inputs {
+        let out = output
+            .clone()
+            .unwrap_or_else(|| input.with_extension("frogc"));
+        match build(input, &out) {
+            Ok(true) => println!("{} -> {}", input.display(), out.display()),
+            Ok(false) => failed = true,
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        }
+    }
+    if failed { 1 } else { 0 }
+}
+
+// Compile one file, printing its errors; Ok(false) if it didn't compile
+fn build(input: &Path, output: &Path) -> Result<bool, String> {
+    let text = fs::read_to_string(input)
+        .map_err(|e| format!("Failed to read {}: {e}", input.display()))?;
+    let doc = Doc::parse(text, 0, &AtomicBool::new(false))
+        .map_err(|e| format!("Failed to parse {}: {e}", input.display()))?;
+
+    match compile(&doc) {
+        Ok(program) => {
+            fs::write(output, program.to_bytes())
+                .map_err(|e| format!("Failed to write {}: {e}", output.display()))?;
+            Ok(true)
+        }
+        Err(errors) => {
+            print_errors(input, &doc, &errors);
+            Ok(false)
+        }
+    }
+}
+
+pub fn print_errors(path: &Path, doc: &Doc, errors: &[CompileError]) {
+    for e in errors {
+        let pos = doc
+            .offset_to_lsp_position(e.range.start)
+            .unwrap_or_default();
+        eprintln!(
+            "{}:{}:{}: error: {}",
+            path.display(),
+            pos.line + 1,
+            pos.character + 1,
+            e.message
+        );
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use lsp_froggy::diagnostics::{
+    COMPILE_ERROR_CODE, INTERNAL_ERROR_CODE, UNUSED_SUPPRESSION_CODE, collect_diagnostics,
+    compile_diagnostics, internal_error_diag,
+};
+use lsp_froggy::document::Doc;
+use lsp_froggy::lint::{LintConfig, RULES};
+use lsp_froggy::project::{CONFIG_CODE, find_config, parse_config};
+use serde_json::{Value, json};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+// Rule id used for diagnostics that don't come from a lint rule
+const SYNTAX_RULE: &str = "syntax-error";
+
+enum Format {
+    Human,
+    Json,
+    Sarif,
+}
+
+struct Report {
+    path: PathBuf,
+    text: String,
+    diagnostics: Vec<Diagnostic>,
+}
+
+pub fn run(args: Vec<String>) -> i32 {
+    let mut format = Format::Human;
+    let mut paths = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("human") => Format::Human,
+                    Some("json") => Format::Json,
+                    Some("sarif") => Format::Sarif,
+                    other => {
+                        eprintln!("Unknown format {:?}; expected human, json or sarif", other);
+                        return 2;
+                    }
+                }
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("Usage: froggy check [--format human|json|sarif] <path>...");
+        return 2;
+    }
+
+    let mut files = Vec::new();
+    for path in &paths {
+        if let Err(e) = collect_files(path, &mut files) {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            return 2;
+        }
+    }
+
+    let reports = match check_files(&files) {
+        Ok(reports) => reports,
+        Err((path, e)) => {
+            eprintln!("Failed to read {}: {}", path.display(), e);
+            return 2;
+        }
+    };
+
+    match format {
+        Format::Human => print_human(&reports),
+        Format::Json => println!("{:#}", to_json(&reports)),
+        Format::Sarif => println!("{:#}", to_sarif(&reports)),
+    }
+
+    let has_errors = reports
+        .iter()
+        .flat_map(|r| &r.diagnostics)
+        .any(|d| d.severity == Some(DiagnosticSeverity::ERROR));
+    if has_errors { 1 } else { 0 }
+}
+
+// Expand directories into the .frog files beneath them, in a stable order
+pub fn collect_files(path: &Path, out: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if !path.is_dir() {
+        fs::metadata(path)?;
+        out.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = fs::read_dir(path)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(&entry, out)?;
+        } else if entry.extension().is_some_and(|ext| ext == "frog") {
+            out.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn check_files(files: &[PathBuf]) -> Result<Vec<Report>, (PathBuf, std::io::Error)> {
+    let mut reports = Vec::new();
+    // Lint config per froggy.toml, with the config file's own problems reported once
+    let mut configs: HashMap<PathBuf, LintConfig> = HashMap::new();
+
+    for path in files {
+        let text = fs::read_to_string(path).map_err(|e| (path.clone(), e))?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        let lints = match find_config(&dir) {
+            Some(config_path) => {
+                if !configs.contains_key(&config_path) {
+                    let config_text =
+                        fs::read_to_string(&config_path).map_err(|e| (config_path.clone(), e))?;
+                    let (config, diagnostics) = parse_config(&config_text);
+                    if !diagnostics.is_empty() {
+                        reports.push(Report {
+                            path: config_path.clone(),
+                            text: config_text,
+                            diagnostics,
+                        });
+                    }
+                    configs.insert(config_path.clone(), config.lints);
+                }
+                configs[&config_path].clone()
+            }
+            None => LintConfig::default(),
+        };
+
+        let (text, mut diagnostics) = match Doc::parse(text.clone(), 0, &AtomicBool::new(false)) {
+            Ok(doc) => {
+                // Files on disk get the server's save-time checks too
+                let mut diagnostics = collect_diagnostics(&doc.tree, &doc, &lints);
+                diagnostics.extend(compile_diagnostics(&doc));
+                (doc.text, diagnostics)
+            }
+            Err(e) => (
+                text,
+                vec![internal_error_diag(format!("Internal error: {e}"))],
+            ),
+        };
+        diagnostics.sort_by_key(|d| (d.range.start.line, d.range.start.character));
+
+        reports.push(Report {
+            path: path.clone(),
+            text,
+            diagnostics,
+        });
+    }
+
+    Ok(reports)
+}
+
+fn severity_name(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        Some(DiagnosticSeverity::HINT) => "hint",
+        _ => "warning",
+    }
+}
+
+fn code(d: &Diagnostic) -> Option<&str> {
+    match &d.code {
+        Some(NumberOrString::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+// Convert an LSP (UTF-16) column into a char index within `line`
+fn utf16_to_char(line: &str, col: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        if units >= col as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+fn print_human(reports: &[Report]) {
+    let (mut errors, mut warnings) = (0, 0);
+
+    for report in reports {
+        let lines: Vec<&str> = report.text.lines().collect();
+
+        for d in &report.diagnostics {
+            match d.severity {
+                Some(DiagnosticSeverity::ERROR) => errors += 1,
+                Some(DiagnosticSeverity::WARNING) => warnings += 1,
+                _ => {}
+            }
+
+            let start = d.range.start;
+            let end = d.range.end;
+            let header = match code(d) {
+                Some(code) => format!("{}[{}]", severity_name(d.severity), code),
+                None => severity_name(d.severity).to_string(),
+            };
+            let line_text = lines.get(start.line as usize).copied().unwrap_or("");
+            let start_col = utf16_to_char(line_text, start.character);
+            let end_col = if end.line == start.line {
+                utf16_to_char(line_text, end.character)
+            } else {
+                line_text.chars().count()
+            };
+
+            let line_no = (start.line + 1).to_string();
+            let gutter = " ".repeat(line_no.len());
+
+            println!("{header}: {}", d.message);
+            println!(
+                "{gutter}--> {}:{}:{}",
+                report.path.display(),
+                start.line + 1,
+                start_col + 1
+            );
+            println!("{gutter} |");
+            println!("{line_no} | {line_text}");
+            println!(
+                "{gutter} | {}{}",
+                " ".repeat(start_col),
+                "^".repeat(end_col.saturating_sub(start_col).max(1))
+            );
+            println!();
+        }
+    }
+
+    println!(
+        "{} file(s) checked: {errors} error(s), {warnings} warning(s)",
+        reports.len()
+    );
+}
+
+fn to_json(reports: &[Report]) -> Value {
+    let items: Vec<Value> = reports
+        .iter()
+        .flat_map(|r| {
+            r.diagnostics.iter().map(|d| {
+                json!({
+                    "file": r.path.display().to_string(),
+                    "severity": severity_name(d.severity),
+                    "code": code(d),
+                    "message": d.message,
+                    "range": d.range,
+                })
+            })
+        })
+        .collect();
+    Value::Array(items)
+}
+
+fn to_sarif(reports: &[Report]) -> Value {
+    let mut rules: Vec<Value> = vec![
+        json!({
+            "id": SYNTAX_RULE,
+            "shortDescription": { "text": "Froggy source could not be parsed" },
+        }),
+        json!({
+            "id": COMPILE_ERROR_CODE,
+            "shortDescription": { "text": "Froggy program could not be compiled" },
+        }),
+        json!({
+            "id": INTERNAL_ERROR_CODE,
+            "shortDescription": { "text": "froggy failed to analyse the file" },
+        }),
+        json!({
+            "id": CONFIG_CODE,
+            "shortDescription": { "text": "froggy.toml contains an invalid setting" },
+        }),
+        json!({
+            "id": UNUSED_SUPPRESSION_CODE,
+            "shortDescription": { "text": "froggy-ignore comment silences nothing" },
+            "defaultConfiguration": { "level": "warning" },
+        }),
+    ];
+    rules.extend(RULES.iter().map(|r| {
+        json!({
+            "id": r.id,
+            "shortDescription": { "text": r.description },
+            "defaultConfiguration": { "level": sarif_level(Some(r.default_severity)) },
+        })
+    }));
+
+    let results: Vec<Value> = reports
+        .iter()
+        .flat_map(|r| {
+            let uri = r.path.display().to_string().replace('\\', "/");
+            r.diagnostics.iter().map(move |d| {
+                json!({
+                    "ruleId": code(d).unwrap_or(SYNTAX_RULE),
+                    "level": sarif_level(d.severity),
+                    "message": { "text": d.message },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": uri },
+                            "region": {
+                                "startLine": d.range.start.line + 1,
+                                "startColumn": d.range.start.character + 1,
+                                "endLine": d.range.end.line + 1,
+                                "endColumn": d.range.end.character + 1,
+                            },
+                        },
+                    }],
+                })
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "froggy",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                },
+            },
+            // LSP positions count UTF-16 code units
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    })
+}
+
+fn sarif_level(severity: Option<DiagnosticSeverity>) -> &'static str {
+    match severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        _ => "note",
+    }
+}
@@ -0,0 +1,68 @@
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use lsp_froggy::bytecode::{Program, disassemble};
+use lsp_froggy::compiler::compile;
+use lsp_froggy::document::Doc;
+
+use crate::build::print_errors;
+
+const USAGE: &str = "Usage: froggy disasm <file.frogc|file.frog>";
+
+pub fn run(args: Vec<String>) -> i32 {
+    let [path] = args.as_slice() else {
+        eprintln!("{USAGE}");
+        return 2;
+    };
+    let path = Path::new(path);
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            eprintln!("Failed to read {}: {e}", path.display());
+            return 2;
+        }
+    };
+
+    // Source files are compiled on the fly; compiled files are shown next to their source, if present
+    let (program, source) = if path.extension().is_some_and(|ext| ext == "frog") {
+        let text = String::from_utf8_lossy(&bytes).into_owned();
+        let doc = match Doc::parse(text, 0, &AtomicBool::new(false)) {
+            Ok(doc) => doc,
+            Err(e) => {
+                eprintln!("Failed to parse {}: {e}", path.display());
+                return 2;
+            }
+        };
+        match compile(&doc) {
+            Ok(program) => (program, Some(doc.text)),
+            Err(errors) => {
+                print_errors(path, &doc, &errors);
+                return 1;
+            }
+        }
+    } else {
+        match Program::from_bytes(&bytes) {
+            Ok(program) => (
+                program,
+                fs::read_to_string(path.with_extension("frog")).ok(),
+            ),
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                return 1;
+            }
+        }
+    };
+
+    match disassemble(&program, source.as_deref()) {
+        Ok(listing) => {
+            print!("{listing}");
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            1
+        }
+    }
+}
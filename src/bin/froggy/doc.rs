@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use lsp_froggy::docgen::{Format, IndexEntry, file_summary, render_index, render_page};
+use lsp_froggy::document::Doc;
+
+use crate::check::collect_files;
+
+const USAGE: &str = "Usage: froggy doc [--format html|markdown] [-o <dir>] <path>...";
+
+pub fn run(args: Vec<String>) -> i32 {
+    let mut format = Format::Html;
+    let mut output = PathBuf::from("doc");
+    let mut paths = Vec::new();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = match args.next().as_deref() {
+                    Some("html") => Format::Html,
+                    Some("markdown" | "md") => Format::Markdown,
+                    other => {
+                        eprintln!("Unknown format {:?}; expected html or markdown", other);
+                        return 2;
+                    }
+                }
+            }
+            "-o" | "--output" => match args.next() {
+                Some(dir) => output = PathBuf::from(dir),
+                None => {
+                    eprintln!("{USAGE}");
+                    return 2;
+                }
+            },
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        return 2;
+    }
+
+    // Pages mirror the layout of the files below each directory given
+    let mut files = Vec::new();
+    for path in &paths {
+        let mut found = Vec::new();
+        if let Err(e) = collect_files(path, &mut found) {
+            eprintln!("Failed to read {}: {e}", path.display());
+            return 2;
+        }
+        let base = if path.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(Path::new(""))
+        };
+        for file in found {
+            let relative = file.strip_prefix(base).unwrap_or(&file).to_path_buf();
+            files.push((file, relative));
+        }
+    }
+
+    // Two inputs can hold files at the same relative path, and would overwrite each
+    // other's page
+    let index = PathBuf::from(format!("index.{}", format.extension()));
+    let mut pages: HashMap<PathBuf, &Path> = HashMap::new();
+    for (file, relative) in &files {
+        let page = relative.with_extension(format.extension());
+        if page == index {
+            eprintln!(
+                "{} would be documented as {}, which is the index page",
+                file.display(),
+                page.display()
+            );
+            return 2;
+        }
+        if let Some(other) = pages.insert(page.clone(), file) {
+            eprintln!(
+                "{} and {} would both be documented as {}; document them separately with -o",
+                other.display(),
+                file.display(),
+                page.display()
+            );
+            return 2;
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (file, relative) in &files {
+        match document(file, relative, &output, format) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        }
+    }
+
+    let index = output.join(format!("index.{}", format.extension()));
+    let written = fs::create_dir_all(&output)
+        .and_then(|()| fs::write(&index, render_index("Froggy programs", &entries, format)));
+    if let Err(e) = written {
+        eprintln!("Failed to write {}: {e}", index.display());
+        return 2;
+    }
+    println!("{}", index.display());
+    0
+}
+
+// Write the page for one file, returning its entry in the index
+fn document(
+    file: &Path,
+    relative: &Path,
+    output: &Path,
+    format: Format,
+) -> Result<IndexEntry, String> {
+    let text =
+        fs::read_to_string(file).map_err(|e| format!("Failed to read {}: {e}", file.display()))?;
+    let doc = Doc::parse(text, 0, &AtomicBool::new(false))
+        .map_err(|e| format!("Failed to parse {}: {e}", file.display()))?;
+
+    let page = relative.with_extension(format.extension());
+    let depth = page.components().count() - 1;
+    let index = format!("{}index.{}", "../".repeat(depth), format.extension());
+    let title = relative.display().to_string();
+
+    let path = output.join(&page);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    fs::write(&path, render_page(&title, &index, &doc, format))
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+
+    Ok(IndexEntry {
+        link: page
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        title,
+        summary: file_summary(&doc),
+    })
+}
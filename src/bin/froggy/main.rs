@@ -0,0 +1,37 @@
+mod build;
+mod check;
+mod disasm;
+mod doc;
+mod repl;
+
+use std::{env, process};
+
+const USAGE: &str = "\
+Usage: froggy <command> [options]
+
+Commands:
+  check [--format human|json|sarif] <path>...   Report diagnostics for .frog files
+  build [-o <out.frogc>] <file.frog>...          Compile to bytecode (.frogc)
+  disasm <file.frogc|file.frog>                  Print bytecode with source lines
+  doc [--format html|markdown] [-o <dir>] <path>...  Generate documentation pages
+  repl [--step-limit <instructions>]             Run Froggy interactively";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let code = match args.next().as_deref() {
+        Some("check") => check::run(args.collect()),
+        Some("build") => build::run(args.collect()),
+        Some("disasm") => disasm::run(args.collect()),
+        Some("doc") => doc::run(args.collect()),
+        Some("repl") => repl::run(args.collect()),
+        Some("-h" | "--help") => {
+            println!("{USAGE}");
+            0
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            2
+        }
+    };
+    process::exit(code);
+}
@@ -0,0 +1,243 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::atomic::AtomicBool;
+
+use lsp_froggy::compiler::CompileError;
+use lsp_froggy::document::Doc;
+use lsp_froggy::interpreter::{Interpreter, Io, Status, Value};
+use lsp_froggy::syntax::syntax_errors;
+
+use crate::build::print_errors;
+
+const HELP: &str = "\
+Enter Froggy instructions; the stack is shown after every line, top of the stack last.
+Labels stay defined across lines, and a jump to a label that isn't defined yet waits for it.
+
+  :stack         Show the stack
+  :step          Toggle step mode: lines are queued, and an empty line runs one instruction
+  :load <file>   Run a .frog file in this session
+  :reset         Forget all labels, instructions and values
+  :help          Show this help
+  :quit          Leave (or end the input)";
+
+const PROMPT: &str = "froggy> ";
+const STEP_PROMPT: &str = "step> ";
+
+// Instructions run per line before giving control back, unless `--step-limit` says
+// otherwise; a loop may never end
+const STEP_LIMIT: usize = 1_000_000;
+
+struct Stdio;
+
+impl Io for Stdio {
+    fn print(&mut self, value: &Value) {
+        println!("{value}");
+    }
+
+    fn read(&mut self) -> Option<String> {
+        print!("? ");
+        io::stdout().flush().ok()?;
+        read_line()
+    }
+}
+
+fn read_line() -> Option<String> {
+    let mut line = String::new();
+    match io::stdin().lock().read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim_end_matches(['\n', '\r']).to_string()),
+    }
+}
+
+pub fn run(args: Vec<String>) -> i32 {
+    let limit = match args.as_slice() {
+        [] => STEP_LIMIT,
+        [flag, n] if flag == "--step-limit" => match n.parse() {
+            Ok(n) if n > 0 => n,
+            _ => {
+                eprintln!("--step-limit needs a positive number of instructions, not `{n}`");
+                return 2;
+            }
+        },
+        _ => {
+            eprintln!("Usage: froggy repl [--step-limit <instructions>]");
+            return 2;
+        }
+    };
+
+    let mut interpreter = Interpreter::new();
+    let mut stepping = false;
+    println!("Froggy REPL; :help lists the commands");
+
+    loop {
+        let prompt = if stepping { STEP_PROMPT } else { PROMPT };
+        print!("{prompt}");
+        let _ = io::stdout().flush();
+        let Some(line) = read_line() else {
+            println!();
+            return 0;
+        };
+        let input = line.trim();
+
+        match input.split_once(' ').unwrap_or((input, "")) {
+            (":quit" | ":q", _) => return 0,
+            (":help" | ":h", _) => println!("{HELP}"),
+            (":stack" | ":s", _) => print_stack(&interpreter),
+            (":reset", _) => {
+                interpreter = Interpreter::new();
+                println!("Cleared");
+            }
+            (":step", _) => {
+                stepping = !stepping;
+                if stepping {
+                    println!("Step mode: lines are queued, and an empty line runs one instruction");
+                    print_next(&interpreter);
+                } else {
+                    println!("Step mode off");
+                    execute(&mut interpreter, limit);
+                }
+            }
+            (":load", path) if !path.trim().is_empty() => {
+                if load(&mut interpreter, Path::new(path.trim())) && !stepping {
+                    execute(&mut interpreter, limit);
+                } else if stepping {
+                    print_next(&interpreter);
+                }
+            }
+            (command, _) if command.starts_with(':') => {
+                println!("Unknown command `{command}`; :help lists the commands");
+            }
+            ("", _) if stepping => {
+                if let Some(next) = interpreter.next_instruction() {
+                    println!("  {next}");
+                }
+                if let Err(e) = interpreter.step(&mut Stdio) {
+                    println!("error: {e}");
+                }
+                print_stack(&interpreter);
+                print_next(&interpreter);
+            }
+            // Enter keeps a loop going that ran out of its budget
+            ("", _) if interpreter.status() == Status::Ready => execute(&mut interpreter, limit),
+            ("", _) => {}
+            _ => {
+                if enter(&mut interpreter, prompt, &line) && !stepping {
+                    execute(&mut interpreter, limit);
+                } else if stepping {
+                    print_next(&interpreter);
+                }
+            }
+        }
+    }
+}
+
+fn parse(text: String) -> Option<Doc> {
+    match Doc::parse(text, 0, &AtomicBool::new(false)) {
+        Ok(doc) => Some(doc),
+        Err(e) => {
+            println!("error: {e}");
+            None
+        }
+    }
+}
+
+// Syntax errors, with the server's messages, in the form load errors come in
+fn syntax_errors_of(doc: &Doc) -> Vec<CompileError> {
+    syntax_errors(&doc.tree, &doc.text)
+        .into_iter()
+        .map(|e| CompileError {
+            range: e.range,
+            message: e.message,
+        })
+        .collect()
+}
+
+// Add a typed line to the session, pointing out its errors below it
+fn enter(interpreter: &mut Interpreter, prompt: &str, line: &str) -> bool {
+    let Some(doc) = parse(line.to_string()) else {
+        return false;
+    };
+    let errors = syntax_errors_of(&doc);
+    let errors = if errors.is_empty() {
+        match interpreter.load(&doc) {
+            Ok(()) => return true,
+            Err(errors) => errors,
+        }
+    } else {
+        errors
+    };
+
+    for e in errors {
+        let width = |offset: usize| line[..offset.min(line.len())].chars().count();
+        let start = width(e.range.start);
+        let len = (width(e.range.end) - start).max(1);
+        println!("{}{}", " ".repeat(prompt.len() + start), "^".repeat(len));
+        println!("error: {}", e.message);
+    }
+    false
+}
+
+fn load(interpreter: &mut Interpreter, path: &Path) -> bool {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            println!("Failed to read {}: {e}", path.display());
+            return false;
+        }
+    };
+    let Some(doc) = parse(text) else {
+        return false;
+    };
+    let errors = syntax_errors_of(&doc);
+    if !errors.is_empty() {
+        print_errors(path, &doc, &errors);
+        return false;
+    }
+    match interpreter.load(&doc) {
+        Ok(()) => true,
+        Err(errors) => {
+            print_errors(path, &doc, &errors);
+            false
+        }
+    }
+}
+
+fn execute(interpreter: &mut Interpreter, limit: usize) {
+    match interpreter.run(limit, &mut Stdio) {
+        Ok(Status::Ready) => {
+            println!("Paused after {limit} instructions; press Enter to keep going or :reset")
+        }
+        Ok(_) => {}
+        Err(e) => println!("error: {e}"),
+    }
+    print_stack(interpreter);
+}
+
+// Forth-style: the depth, then the values from the bottom up
+fn print_stack(interpreter: &Interpreter) {
+    let values: Vec<String> = interpreter
+        .stack()
+        .iter()
+        .map(|v| match v {
+            Value::Str(s) => format!("{s:?}"),
+            v => v.to_string(),
+        })
+        .collect();
+    let mut line = format!("<{}>", values.len());
+    for v in &values {
+        line.push(' ');
+        line.push_str(v);
+    }
+    if let Status::Waiting(label) = interpreter.status() {
+        line.push_str(&format!("  (waiting for `LILY {label}`)"));
+    }
+    println!("{line}");
+}
+
+fn print_next(interpreter: &Interpreter) {
+    match interpreter.next_instruction() {
+        Some(next) => println!("next: {next}"),
+        None => println!("next: (nothing queued)"),
+    }
+}
@@ -0,0 +1,338 @@
+// Compiled Froggy programs and their `.frogc` file format (see "Bytecode" in the README).
+//
+// All integers are little-endian:
+//
+//   magic     4 bytes   "FROG"
+//   version   u16       FORMAT_VERSION
+//   flags     u16       0, reserved
+//   strings   u32 count, then per string: u32 byte length, UTF-8 bytes
+//   code      u32 byte length, then the instructions
+//   lines     u32 count, then per instruction: u32 code offset, u32 source line (1-based)
+//
+// Each instruction is a one-byte opcode followed by its operand, if any.
+// Jump operands are absolute code offsets.
+
+use std::fmt;
+
+pub const MAGIC: &[u8; 4] = b"FROG";
+pub const FORMAT_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instr {
+    PushInt(i64),
+    PushFloat(f64),
+    // Index into the string table
+    PushStr(u32),
+    Pop,
+    Inc,
+    Dec,
+    Dup,
+    Swap,
+    Over,
+    Print,
+    Read,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Jump(u32),
+    // Pop a value; jump if it is zero
+    JumpIfZero(u32),
+}
+
+impl Instr {
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Self::PushInt(_) => 0x01,
+            Self::PushFloat(_) => 0x02,
+            Self::PushStr(_) => 0x03,
+            Self::Pop => 0x04,
+            Self::Inc => 0x05,
+            Self::Dec => 0x06,
+            Self::Dup => 0x07,
+            Self::Swap => 0x08,
+            Self::Over => 0x09,
+            Self::Print => 0x0a,
+            Self::Read => 0x0b,
+            Self::Add => 0x10,
+            Self::Sub => 0x11,
+            Self::Mul => 0x12,
+            Self::Div => 0x13,
+            Self::Eq => 0x18,
+            Self::Ne => 0x19,
+            Self::Lt => 0x1a,
+            Self::Gt => 0x1b,
+            Self::Le => 0x1c,
+            Self::Ge => 0x1d,
+            Self::Jump(_) => 0x20,
+            Self::JumpIfZero(_) => 0x21,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Self::PushInt(_) => "push_int",
+            Self::PushFloat(_) => "push_float",
+            Self::PushStr(_) => "push_str",
+            Self::Pop => "pop",
+            Self::Inc => "inc",
+            Self::Dec => "dec",
+            Self::Dup => "dup",
+            Self::Swap => "swap",
+            Self::Over => "over",
+            Self::Print => "print",
+            Self::Read => "read",
+            Self::Add => "add",
+            Self::Sub => "sub",
+            Self::Mul => "mul",
+            Self::Div => "div",
+            Self::Eq => "eq",
+            Self::Ne => "ne",
+            Self::Lt => "lt",
+            Self::Gt => "gt",
+            Self::Le => "le",
+            Self::Ge => "ge",
+            Self::Jump(_) => "jump",
+            Self::JumpIfZero(_) => "jump_if_zero",
+        }
+    }
+
+    // Encoded size in bytes
+    pub fn size(&self) -> usize {
+        1 + match self {
+            Self::PushInt(_) | Self::PushFloat(_) => 8,
+            Self::PushStr(_) | Self::Jump(_) | Self::JumpIfZero(_) => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        out.push(self.opcode());
+        match *self {
+            Self::PushInt(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Self::PushFloat(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Self::PushStr(i) | Self::Jump(i) | Self::JumpIfZero(i) => {
+                out.extend_from_slice(&i.to_le_bytes())
+            }
+            _ => {}
+        }
+    }
+
+    // Decode the instruction at `offset` of `code`
+    pub fn decode(code: &[u8], offset: usize) -> Result<Self, DecodeError> {
+        let opcode = *code.get(offset).ok_or(DecodeError::Truncated)?;
+        let operand = |len: usize| {
+            code.get(offset + 1..offset + 1 + len)
+                .ok_or(DecodeError::Truncated)
+        };
+        let u32_at = || -> Result<u32, DecodeError> {
+            Ok(u32::from_le_bytes(operand(4)?.try_into().unwrap()))
+        };
+
+        Ok(match opcode {
+            0x01 => Self::PushInt(i64::from_le_bytes(operand(8)?.try_into().unwrap())),
+            0x02 => Self::PushFloat(f64::from_le_bytes(operand(8)?.try_into().unwrap())),
+            0x03 => Self::PushStr(u32_at()?),
+            0x04 => Self::Pop,
+            0x05 => Self::Inc,
+            0x06 => Self::Dec,
+            0x07 => Self::Dup,
+            0x08 => Self::Swap,
+            0x09 => Self::Over,
+            0x0a => Self::Print,
+            0x0b => Self::Read,
+            0x10 => Self::Add,
+            0x11 => Self::Sub,
+            0x12 => Self::Mul,
+            0x13 => Self::Div,
+            0x18 => Self::Eq,
+            0x19 => Self::Ne,
+            0x1a => Self::Lt,
+            0x1b => Self::Gt,
+            0x1c => Self::Le,
+            0x1d => Self::Ge,
+            0x20 => Self::Jump(u32_at()?),
+            0x21 => Self::JumpIfZero(u32_at()?),
+            other => {
+                return Err(DecodeError::UnknownOpcode {
+                    offset,
+                    opcode: other,
+                });
+            }
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+    InvalidString(u32),
+    UnknownOpcode { offset: usize, opcode: u8 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a .frogc file"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported format version {v}"),
+            Self::Truncated => write!(f, "file is truncated"),
+            Self::InvalidString(i) => write!(f, "string {i} is not valid UTF-8"),
+            Self::UnknownOpcode { offset, opcode } => {
+                write!(f, "unknown opcode {opcode:#04x} at {offset:#06x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+    pub strings: Vec<String>,
+    pub code: Vec<u8>,
+    // (code offset, 1-based source line) of every instruction, in code order
+    pub lines: Vec<(u32, u32)>,
+}
+
+impl Program {
+    // Decoded instructions with their code offsets
+    pub fn instructions(&self) -> Result<Vec<(usize, Instr)>, DecodeError> {
+        let mut out = Vec::new();
+        let mut offset = 0;
+        while offset < self.code.len() {
+            let instr = Instr::decode(&self.code, offset)?;
+            out.push((offset, instr));
+            offset += instr.size();
+        }
+        Ok(out)
+    }
+
+    pub fn line_of(&self, offset: usize) -> Option<u32> {
+        self.lines
+            .binary_search_by_key(&(offset as u32), |&(o, _)| o)
+            .ok()
+            .map(|i| self.lines[i].1)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+
+        out.extend_from_slice(&(self.strings.len() as u32).to_le_bytes());
+        for s in &self.strings {
+            out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+
+        out.extend_from_slice(&(self.code.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.code);
+
+        out.extend_from_slice(&(self.lines.len() as u32).to_le_bytes());
+        for (offset, line) in &self.lines {
+            out.extend_from_slice(&offset.to_le_bytes());
+            out.extend_from_slice(&line.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader { bytes, pos: 0 };
+        if r.take(4)? != MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = u16::from_le_bytes(r.take(2)?.try_into().unwrap());
+        if version != FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        r.take(2)?;
+
+        let count = r.u32()?;
+        let mut strings = Vec::new();
+        for i in 0..count {
+            let len = r.u32()? as usize;
+            let s = std::str::from_utf8(r.take(len)?).map_err(|_| DecodeError::InvalidString(i))?;
+            strings.push(s.to_string());
+        }
+
+        let len = r.u32()? as usize;
+        let code = r.take(len)?.to_vec();
+
+        let count = r.u32()?;
+        let mut lines = Vec::new();
+        for _ in 0..count {
+            lines.push((r.u32()?, r.u32()?));
+        }
+
+        Ok(Self {
+            strings,
+            code,
+            lines,
+        })
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(DecodeError::Truncated)?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+// One line per instruction: offset, mnemonic, operand and source line.
+// With the source at hand, each line also shows the statement it came from.
+pub fn disassemble(program: &Program, source: Option<&str>) -> Result<String, DecodeError> {
+    let source_lines: Vec<&str> = source.map(|s| s.lines().collect()).unwrap_or_default();
+    let mut out = String::new();
+
+    for (offset, instr) in program.instructions()? {
+        let operand = match instr {
+            Instr::PushInt(n) => n.to_string(),
+            Instr::PushFloat(x) => format!("{x:?}"),
+            Instr::PushStr(i) => match program.strings.get(i as usize) {
+                Some(s) => format!("{s:?}"),
+                None => format!("#{i} (missing)"),
+            },
+            Instr::Jump(target) | Instr::JumpIfZero(target) => format!("{target:#06x}"),
+            _ => String::new(),
+        };
+        let mut line = format!("{offset:#06x}  {:<13}{operand}", instr.mnemonic());
+
+        if let Some(n) = program.line_of(offset) {
+            let width = line.len().max(34);
+            line = format!("{line:<width$}; line {n}");
+            if let Some(text) = (n as usize)
+                .checked_sub(1)
+                .and_then(|i| source_lines.get(i))
+            {
+                line.push_str(": ");
+                line.push_str(text.trim());
+            }
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    Ok(out)
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use tower_lsp::lsp_types::*;
+
+use crate::diagnostics::{INSERT_KEY, REPLACEMENT_KEY};
+use crate::syntax::Operand;
+
+#[derive(Deserialize)]
+struct Insert {
+    position: Position,
+    operand: Operand,
+}
+
+// LSP 3.18 snippet edits, which lsp-types 0.94 doesn't define yet. Only what the quick
+// fixes send is declared.
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetCodeAction {
+    pub title: String,
+    pub kind: CodeActionKind,
+    pub diagnostics: Vec<Diagnostic>,
+    pub edit: SnippetWorkspaceEdit,
+    pub is_preferred: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetWorkspaceEdit {
+    pub document_changes: Vec<SnippetDocumentEdit>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnippetDocumentEdit {
+    pub text_document: OptionalVersionedTextDocumentIdentifier,
+    pub edits: Vec<OneOf<TextEdit, SnippetTextEdit>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnippetTextEdit {
+    pub range: Range,
+    pub snippet: StringValue,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StringValue {
+    // Always "snippet"
+    pub kind: &'static str,
+    pub value: String,
+}
+
+// A quick fix, before it is put in the form the client understands
+struct Fix {
+    title: String,
+    diag: Diagnostic,
+    edit: OneOf<TextEdit, SnippetTextEdit>,
+    preferred: bool,
+}
+
+// Quick fixes for the diagnostics the client sent with a code action request.
+// Fixes are carried in the diagnostics themselves, so they work in any document kind;
+// `labels` are the labels defined in the program, in source order, to jump to.
+pub fn quick_fixes(
+    uri: &Url,
+    diagnostics: &[Diagnostic],
+    labels: &[String],
+) -> Vec<CodeActionOrCommand> {
+    fixes(diagnostics, labels, false)
+        .into_iter()
+        .filter_map(|fix| {
+            let OneOf::Left(edit) = fix.edit else {
+                return None;
+            };
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: fix.title,
+                kind: Some(CodeActionKind::QUICKFIX),
+                diagnostics: Some(vec![fix.diag]),
+                edit: Some(WorkspaceEdit {
+                    changes: Some(HashMap::from([(uri.clone(), vec![edit])])),
+                    ..Default::default()
+                }),
+                is_preferred: Some(fix.preferred),
+                ..Default::default()
+            }))
+        })
+        .collect()
+}
+
+// The same fixes for clients with `snippetEditSupport`: missing operands are inserted
+// as tab stops, and a jump target as a choice of the labels
+pub fn snippet_quick_fixes(
+    uri: &Url,
+    diagnostics: &[Diagnostic],
+    labels: &[String],
+) -> Vec<SnippetCodeAction> {
+    fixes(diagnostics, labels, true)
+        .into_iter()
+        .map(|fix| SnippetCodeAction {
+            title: fix.title,
+            kind: CodeActionKind::QUICKFIX,
+            diagnostics: vec![fix.diag],
+            edit: SnippetWorkspaceEdit {
+                document_changes: vec![SnippetDocumentEdit {
+                    text_document: OptionalVersionedTextDocumentIdentifier {
+                        uri: uri.clone(),
+                        version: None,
+                    },
+                    edits: vec![fix.edit],
+                }],
+            },
+            is_preferred: fix.preferred,
+        })
+        .collect()
+}
+
+fn fixes(diagnostics: &[Diagnostic], labels: &[String], snippets: bool) -> Vec<Fix> {
+    let mut fixes = Vec::new();
+    for diag in diagnostics {
+        let Some(data) = &diag.data else {
+            continue;
+        };
+        let mut push = |title: String, edit, preferred| {
+            fixes.push(Fix {
+                title,
+                diag: diag.clone(),
+                edit,
+                preferred,
+            })
+        };
+
+        if let Some(replacement) = data.get(REPLACEMENT_KEY).and_then(|r| r.as_str()) {
+            let edit = TextEdit::new(diag.range, replacement.to_string());
+            push(
+                format!("Replace with `{replacement}`"),
+                OneOf::Left(edit),
+                true,
+            );
+        }
+
+        let Some(insert) = data
+            .get(INSERT_KEY)
+            .and_then(|i| serde_json::from_value::<Insert>(i.clone()).ok())
+        else {
+            continue;
+        };
+        let at = Range::new(insert.position, insert.position);
+        let text = |text: String| OneOf::Left(TextEdit::new(at, text));
+        let snippet = |value: String| {
+            OneOf::Right(SnippetTextEdit {
+                range: at,
+                snippet: StringValue {
+                    kind: "snippet",
+                    value,
+                },
+            })
+        };
+        match insert.operand {
+            Operand::Value if snippets => {
+                push(
+                    "Insert value `0`".to_string(),
+                    snippet(" ${1:0}".to_string()),
+                    true,
+                );
+            }
+            Operand::Value => {
+                push("Insert value `0`".to_string(), text(" 0".to_string()), true);
+            }
+            // Label names can't hold the characters snippets escape
+            Operand::Target if snippets && labels.len() > 1 => {
+                let choice = format!(" ${{1|{}|}}", labels.join(","));
+                push("Insert target".to_string(), snippet(choice), true);
+            }
+            Operand::Target => {
+                for label in labels {
+                    let title = format!("Insert target `{label}`");
+                    let edit = if snippets {
+                        snippet(format!(" ${{1:{label}}}"))
+                    } else {
+                        text(format!(" {label}"))
+                    };
+                    push(title, edit, labels.len() == 1);
+                }
+            }
+            Operand::LabelName => {
+                let name = unused_label_name(labels);
+                let title = format!("Insert label name `{name}`");
+                let edit = if snippets {
+                    snippet(format!(" ${{1:{name}}}"))
+                } else {
+                    text(format!(" {name}"))
+                };
+                push(title, edit, true);
+            }
+        }
+    }
+    fixes
+}
+
+// `label`, or `label2`, `label3`... if that's taken
+fn unused_label_name(labels: &[String]) -> String {
+    (1..)
+        .map(|n| match n {
+            1 => "label".to_string(),
+            n => format!("label{n}"),
+        })
+        .find(|name| !labels.contains(name))
+        .unwrap()
+}
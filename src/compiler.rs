@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use tree_sitter::Node;
+
+use crate::bytecode::{Instr, Program};
+use crate::document::{ByteRange, Doc};
+use crate::syntax::syntax_errors;
+use crate::utils::froggy_helpers::program_instructions;
+use crate::utils::tree_sitter_helpers::node_byte_range;
+
+#[derive(Debug)]
+pub struct CompileError {
+    pub range: ByteRange,
+    pub message: String,
+}
+
+// Lower a parsed document to bytecode. Programs with syntax errors aren't compiled.
+pub fn compile(doc: &Doc) -> Result<Program, Vec<CompileError>> {
+    let errors = syntax_errors(&doc.tree, &doc.text);
+    if !errors.is_empty() {
+        return Err(errors
+            .into_iter()
+            .map(|e| CompileError {
+                range: e.range,
+                message: e.message,
+            })
+            .collect());
+    }
+
+    let mut program = Program::default();
+    let mut errors = Vec::new();
+    // Instructions with their source node; jump targets are filled in below
+    let mut lowered: Vec<(Instr, Node)> = Vec::new();
+    // Code offset of every label definition, by the definition's start byte
+    let mut label_offsets: HashMap<usize, u32> = HashMap::new();
+    let mut offset = 0;
+
+    for node in program_instructions(&doc.tree) {
+        if node.kind() == "label_definition" {
+            label_offsets.insert(node.start_byte(), offset);
+            continue;
+        }
+        match lower(node, doc, &mut program.strings) {
+            Ok(instr) => {
+                offset += instr.size() as u32;
+                lowered.push((instr, node));
+            }
+            Err(e) => errors.push(e),
+        }
+    }
+
+    for (instr, node) in &mut lowered {
+        let (Instr::Jump(target) | Instr::JumpIfZero(target)) = instr else {
+            continue;
+        };
+        let Some(name_node) = node.child_by_field_name("target") else {
+            continue;
+        };
+        let name = name_node.utf8_text(doc.text.as_bytes()).unwrap_or("");
+        // Jumps go where go-to-definition does
+        match doc
+            .index()
+            .label_defs
+            .get(name)
+            .and_then(|def| label_offsets.get(&def.start))
+        {
+            Some(&offset) => *target = offset,
+            None => errors.push(CompileError {
+                range: node_byte_range(name_node),
+                message: format!("undefined label `{name}`"),
+            }),
+        }
+    }
+
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| e.range.start);
+        return Err(errors);
+    }
+
+    for (instr, node) in lowered {
+        let line = node.start_position().row as u32 + 1;
+        program.lines.push((program.code.len() as u32, line));
+        instr.encode(&mut program.code);
+    }
+    Ok(program)
+}
+
+// Bytecode for one instruction node; jump targets are left at 0
+pub(crate) fn lower(
+    node: Node,
+    doc: &Doc,
+    strings: &mut Vec<String>,
+) -> Result<Instr, CompileError> {
+    Ok(match node.kind() {
+        "plop" => return lower_plop(node, doc, strings),
+        "splash" => Instr::Pop,
+        "gulp" => Instr::Inc,
+        "burp" => Instr::Dec,
+        "dup" => Instr::Dup,
+        "swap" => Instr::Swap,
+        "over" => Instr::Over,
+        "ribbit" => Instr::Print,
+        "croak" => Instr::Read,
+        "add" => Instr::Add,
+        "sub" => Instr::Sub,
+        "mul" => Instr::Mul,
+        "div" => Instr::Div,
+        "equals" => Instr::Eq,
+        "not_equal" => Instr::Ne,
+        "less_than" => Instr::Lt,
+        "greater_than" => Instr::Gt,
+        "less_eq" => Instr::Le,
+        "greater_eq" => Instr::Ge,
+        "hop" => Instr::Jump(0),
+        "leap" => Instr::JumpIfZero(0),
+        other => {
+            return Err(CompileError {
+                range: node_byte_range(node),
+                message: format!("cannot compile `{other}`"),
+            });
+        }
+    })
+}
+
+fn lower_plop(node: Node, doc: &Doc, strings: &mut Vec<String>) -> Result<Instr, CompileError> {
+    let error = |message: String| CompileError {
+        range: node_byte_range(node),
+        message,
+    };
+    let value = node
+        .named_child(0)
+        .ok_or_else(|| error("PLOP without a value".to_string()))?;
+    let text = value.utf8_text(doc.text.as_bytes()).unwrap_or("");
+
+    match value.kind() {
+        "number" if text.contains('.') => text
+            .parse()
+            .map(Instr::PushFloat)
+            .map_err(|_| error(format!("invalid number `{text}`"))),
+        "number" => text
+            .parse()
+            .map(Instr::PushInt)
+            .map_err(|_| error(format!("`{text}` does not fit in 64 bits"))),
+        "string" => {
+            let s = unescape(&text[1..text.len() - 1]);
+            // Identical strings share one table entry
+            let index = match strings.iter().position(|existing| *existing == s) {
+                Some(i) => i,
+                None => {
+                    strings.push(s);
+                    strings.len() - 1
+                }
+            };
+            Ok(Instr::PushStr(index as u32))
+        }
+        other => Err(error(format!("cannot push `{other}`"))),
+    }
+}
+
+// Resolve `\n`, `\t`, `\"` and `\\`; any other escaped character stands for itself
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,57 @@
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, Documentation, MarkupContent, MarkupKind, Position,
+};
+
+use crate::document::Doc;
+use crate::utils::froggy_helpers::{MNEMONICS, label_names};
+
+// Labels after HOP or LEAP, and mnemonics where an instruction can start
+pub fn completions_at(doc: &Doc, position: Position) -> Vec<CompletionItem> {
+    let Some(offset) = doc.lsp_position_to_offset(position) else {
+        return Vec::new();
+    };
+    let line_start = doc.text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let before = &doc.text[line_start..offset];
+    if before.contains("//") {
+        return Vec::new();
+    }
+
+    // Words before the one being typed
+    let mut words: Vec<&str> = before.split_whitespace().collect();
+    if !before.is_empty() && !before.ends_with(char::is_whitespace) {
+        words.pop();
+    }
+    match words.as_slice() {
+        [] => MNEMONICS
+            .iter()
+            .map(|m| CompletionItem {
+                label: m.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            })
+            .collect(),
+        [jump] if jump.eq_ignore_ascii_case("HOP") || jump.eq_ignore_ascii_case("LEAP") => {
+            label_items(doc)
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn label_items(doc: &Doc) -> Vec<CompletionItem> {
+    let index = doc.index();
+    label_names(index)
+        .into_iter()
+        .map(|name| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::FUNCTION),
+            detail: index.stack_effects.get(name).map(|e| e.to_string()),
+            documentation: index.label_docs.get(name).map(|docs| {
+                Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: docs.clone(),
+                })
+            }),
+            ..Default::default()
+        })
+        .collect()
+}
@@ -1,7 +1,12 @@
+use serde_json::json;
 use tower_lsp::lsp_types::*;
 use tree_sitter::{Node, Tree};
 
+use crate::compiler::compile;
 use crate::document::Doc;
+use crate::lint::{LintConfig, find_rule, run_lints};
+use crate::syntax::{SyntaxError, syntax_errors};
+use crate::utils::tree_sitter_helpers::{dfs_visit, labeldef_to_range};
 
 fn node_range(node: Node, doc: &Doc) -> Range {
     Range {
@@ -14,36 +19,234 @@ fn node_range(node: Node, doc: &Doc) -> Range {
     }
 }
 
-fn syntax_error_diag(node: Node, doc: &Doc) -> Diagnostic {
-    let snippet = node.utf8_text(doc.text.as_bytes()).unwrap_or("__unknown__");
+// Keys of a syntax error's `data`, read back by the quick fixes: the text to replace
+// its range with, or where an incomplete instruction's operand goes
+pub const REPLACEMENT_KEY: &str = "replacement";
+pub const INSERT_KEY: &str = "insert";
+
+fn syntax_error_diag(error: SyntaxError, doc: &Doc) -> Diagnostic {
+    let data = match (error.suggestion, error.insertion) {
+        (Some(mnemonic), _) => Some(json!({ REPLACEMENT_KEY: mnemonic })),
+        (None, Some(insertion)) => Some(json!({
+            INSERT_KEY: {
+                "position": doc.offset_to_lsp_position(insertion.at).unwrap_or_default(),
+                "operand": insertion.operand,
+            }
+        })),
+        (None, None) => None,
+    };
     Diagnostic {
-        range: node_range(node, doc),
+        range: labeldef_to_range(&error.range, doc),
         severity: Some(DiagnosticSeverity::ERROR),
         source: Some("froggy".to_string()),
-        message: format!("Syntax error near `{snippet}`"),
+        message: error.message,
+        data,
         ..Default::default()
     }
 }
 
-pub fn collect_diagnostics(tree: &Tree, doc: &Doc) -> Vec<Diagnostic> {
-    let mut out = Vec::new();
-    let mut stack = vec![tree.root_node()];
+// Code for diagnostics reporting a failure of the server itself
+pub const INTERNAL_ERROR_CODE: &str = "internal-error";
 
-    eprintln!("=== DEBUG: Starting diagnostic collection ===");
-    eprintln!("Tree root kind: {}", tree.root_node().kind());
-    eprintln!("Tree has_error: {}", tree.root_node().has_error());
+// Diagnostic shown at the top of a file the server could not analyse
+pub fn internal_error_diag(message: String) -> Diagnostic {
+    Diagnostic {
+        range: Range::default(),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(INTERNAL_ERROR_CODE.to_string())),
+        source: Some("froggy".to_string()),
+        message,
+        ..Default::default()
+    }
+}
 
-    while let Some(node) = stack.pop() {
-        let is_err = node.is_error() || node.is_missing() || node.kind() == "ERROR";
-        if is_err {
-            eprintln!(">>> FOUND ERROR NODE: {}", node.kind());
-            out.push(syntax_error_diag(node, doc));
-        }
+// Code for problems found by compiling the whole program, such as undefined labels
+pub const COMPILE_ERROR_CODE: &str = "compile-error";
 
-        let mut cursor = node.walk();
-        stack.extend(node.children(&mut cursor));
+// Diagnostics from a full compile; too slow to run on every keystroke, so the
+// server runs it on save. Files with syntax errors already have diagnostics.
+pub fn compile_diagnostics(doc: &Doc) -> Vec<Diagnostic> {
+    if doc.tree.root_node().has_error() {
+        return Vec::new();
     }
+    let Err(errors) = compile(doc) else {
+        return Vec::new();
+    };
+    errors
+        .into_iter()
+        .map(|e| Diagnostic {
+            range: labeldef_to_range(&e.range, doc),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: Some(NumberOrString::String(COMPILE_ERROR_CODE.to_string())),
+            source: Some("froggy".to_string()),
+            message: e.message,
+            ..Default::default()
+        })
+        .collect()
+}
+
+pub fn collect_diagnostics(tree: &Tree, doc: &Doc, lints: &LintConfig) -> Vec<Diagnostic> {
+    let mut out: Vec<Diagnostic> = syntax_errors(tree, &doc.text)
+        .into_iter()
+        .map(|e| syntax_error_diag(e, doc))
+        .collect();
+
+    out.extend(apply_suppressions(tree, doc, lints, run_lints(doc, lints)));
 
-    eprintln!("=== Total diagnostics found: {} ===", out.len());
     out
 }
+
+// Rules named by a `froggy-ignore` comment; an empty list means every rule
+struct Suppression<'tree> {
+    comment: Node<'tree>,
+    // Line whose diagnostics are dropped, or None for the whole file
+    line: Option<u32>,
+    rules: Vec<String>,
+    used: Vec<bool>,
+}
+
+impl Suppression<'_> {
+    fn matches(&mut self, rule: &str, line: u32) -> bool {
+        if self.line.is_some_and(|l| l != line) {
+            return false;
+        }
+        if self.rules.is_empty() {
+            self.used.fill(true);
+            return true;
+        }
+        match self.rules.iter().position(|r| r == rule) {
+            Some(i) => {
+                self.used[i] = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Parse `// froggy-ignore: a, b` / `// froggy-ignore-file: a` into (is_file, rules)
+fn parse_suppression(comment: &str) -> Option<(bool, Vec<String>)> {
+    let body = comment.strip_prefix("//")?.trim();
+    let (file_wide, rest) = match body.strip_prefix("froggy-ignore-file") {
+        Some(rest) => (true, rest),
+        None => (false, body.strip_prefix("froggy-ignore")?),
+    };
+    let rest = rest.trim();
+    let list = match rest.strip_prefix(':') {
+        Some(list) => list,
+        None if rest.is_empty() => "",
+        None => return None,
+    };
+
+    let rules = list
+        .split(',')
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .map(str::to_string)
+        .collect();
+    Some((file_wide, rules))
+}
+
+// Code for `froggy-ignore` comments that silence nothing
+pub const UNUSED_SUPPRESSION_CODE: &str = "unused-suppression";
+
+fn suppression_warning(node: Node, doc: &Doc, message: String) -> Diagnostic {
+    Diagnostic {
+        range: node_range(node, doc),
+        severity: Some(DiagnosticSeverity::WARNING),
+        code: Some(NumberOrString::String(UNUSED_SUPPRESSION_CODE.to_string())),
+        source: Some("froggy".to_string()),
+        message,
+        tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+        ..Default::default()
+    }
+}
+
+// Drop lint diagnostics silenced by `froggy-ignore` comments, and warn about
+// suppressions that no longer silence anything
+fn apply_suppressions(
+    tree: &Tree,
+    doc: &Doc,
+    lints: &LintConfig,
+    diags: Vec<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let root = tree.root_node();
+    let first_code = {
+        let mut cursor = root.walk();
+        root.children(&mut cursor)
+            .find(|n| n.kind() != "comment")
+            .map_or(usize::MAX, |n| n.start_byte())
+    };
+
+    let mut warnings = Vec::new();
+    let mut suppressions = Vec::new();
+    dfs_visit(tree, |node| {
+        if node.kind() != "comment" {
+            return;
+        }
+        let text = node.utf8_text(doc.text.as_bytes()).unwrap_or("");
+        let Some((file_wide, rules)) = parse_suppression(text) else {
+            return;
+        };
+
+        if file_wide && node.start_byte() > first_code {
+            warnings.push(suppression_warning(
+                node,
+                doc,
+                "`froggy-ignore-file` only applies before the first instruction".to_string(),
+            ));
+            return;
+        }
+
+        let line = doc
+            .offset_to_lsp_position(node.start_byte())
+            .unwrap_or_default()
+            .line;
+        suppressions.push(Suppression {
+            comment: node,
+            line: (!file_wide).then_some(line + 1),
+            used: vec![false; rules.len().max(1)],
+            rules,
+        });
+    });
+
+    let mut kept = diags;
+    kept.retain(|d| {
+        let Some(NumberOrString::String(rule)) = &d.code else {
+            return true;
+        };
+        let line = d.range.start.line;
+        // Check every suppression so overlapping ones are all marked used
+        let mut suppressed = false;
+        for s in suppressions.iter_mut() {
+            suppressed |= s.matches(rule, line);
+        }
+        !suppressed
+    });
+
+    for s in &suppressions {
+        if s.rules.is_empty() {
+            if !s.used[0] {
+                warnings.push(suppression_warning(
+                    s.comment,
+                    doc,
+                    "Suppression comment does not silence any diagnostic".to_string(),
+                ));
+            }
+            continue;
+        }
+        for (rule, used) in s.rules.iter().zip(&s.used) {
+            let message = match find_rule(rule) {
+                None => format!("Unknown lint rule `{rule}` in suppression comment"),
+                // Disabled rules never fire, so their suppressions can't be stale
+                Some(r) if lints.severity(r).is_none() => continue,
+                Some(_) if !used => format!("Suppression of `{rule}` is no longer needed"),
+                Some(_) => continue,
+            };
+            warnings.push(suppression_warning(s.comment, doc, message));
+        }
+    }
+
+    kept.extend(warnings);
+    kept
+}
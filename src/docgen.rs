@@ -0,0 +1,373 @@
+// Static documentation pages for Froggy programs, as written by `froggy doc`: per file,
+// a table of its labels and its source, highlighted like the editor does.
+
+use std::fmt::Write;
+
+use crate::document::{Doc, doc_comment};
+use crate::semantic_tokens::{build_semantic_tokens, legend, token_modifiers};
+use crate::utils::froggy_helpers::{label_names, program_instructions};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+// What a page says about one label
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LabelInfo {
+    pub name: String,
+    // 1-based line of the definition
+    pub line: u32,
+    pub docs: Option<String>,
+    pub declared: Option<String>,
+    // From the block's instructions, in the annotation's notation: `( 1 -- 2 )`
+    pub inferred: String,
+    // Labels the block jumps to, in order of the jumps
+    pub jumps_to: Vec<String>,
+    // 1-based lines of the jumps to the label
+    pub jumped_from: Vec<u32>,
+}
+
+fn line_of(doc: &Doc, offset: usize) -> u32 {
+    doc.offset_to_lsp_position(offset).map_or(0, |p| p.line) + 1
+}
+
+pub fn label_infos(doc: &Doc) -> Vec<LabelInfo> {
+    let index = doc.index();
+    let cfg = doc.cfg();
+    let instrs = program_instructions(&doc.tree);
+    let bytes = doc.text.as_bytes();
+
+    label_names(index)
+        .into_iter()
+        .filter_map(|name| {
+            let def = &index.label_defs[name];
+            let label = cfg.nodes.iter().position(|n| n.range.start == def.start)?;
+
+            let effect = doc.stack_analysis().blocks.get(name)?;
+            let inferred = match effect.net {
+                Some(net) => format!(
+                    "( {} -- {} )",
+                    effect.consumes,
+                    effect.consumes as i64 + net
+                ),
+                None => "varies by exit".to_string(),
+            };
+
+            let mut jumps_to = Vec::new();
+            for node in instrs[label + 1..]
+                .iter()
+                .take_while(|n| n.kind() != "label_definition")
+            {
+                if let Some(target) = node.child_by_field_name("target")
+                    && let Ok(target) = target.utf8_text(bytes)
+                    && !jumps_to.iter().any(|t| t == target)
+                {
+                    jumps_to.push(target.to_string());
+                }
+            }
+
+            let mut jumped_from: Vec<u32> = index
+                .label_refs
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|r| line_of(doc, r.start))
+                .collect();
+            jumped_from.sort_unstable();
+
+            Some(LabelInfo {
+                name: name.to_string(),
+                line: line_of(doc, def.start),
+                docs: index.label_docs.get(name).cloned(),
+                declared: index.stack_effects.get(name).map(|e| e.to_string()),
+                inferred,
+                jumps_to,
+                jumped_from,
+            })
+        })
+        .collect()
+}
+
+// The comments opening a file, unless they document its first label
+pub fn file_summary(doc: &Doc) -> Option<String> {
+    let root = doc.tree.root_node();
+    let mut cursor = root.walk();
+    let mut comments = Vec::new();
+    let mut next_row = 0;
+    for node in root.children(&mut cursor) {
+        if node.kind() != "comment" || node.start_position().row != next_row {
+            let labelled = node.kind() == "statement"
+                && node.start_position().row == next_row
+                && node
+                    .named_child(0)
+                    .is_some_and(|c| c.kind() == "label_definition");
+            if labelled {
+                return None;
+            }
+            break;
+        }
+        comments.push(node);
+        next_row = node.end_position().row + 1;
+    }
+    doc_comment(&comments, doc.text.as_bytes())
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// The source as `<pre>` HTML, one `L<n>` anchor per line and a `tok-<type>` class per
+// semantic token
+pub fn highlight(doc: &Doc) -> String {
+    let legend = legend();
+    let mut toks: Vec<_> = build_semantic_tokens(doc)
+        .into_iter()
+        .filter_map(|t| Some((t.byte_range(doc)?, t.token_type(), t.modifiers())))
+        .collect();
+    toks.sort_by_key(|(r, _, _)| r.start);
+
+    let mut out = String::from("<pre class=\"source\"><code>");
+    let mut toks = toks.into_iter().peekable();
+    let mut start = 0;
+    for (n, line) in doc.text.split_inclusive('\n').enumerate() {
+        let end = start + line.trim_end_matches(['\n', '\r']).len();
+        let _ = write!(out, "<span class=\"line\" id=\"L{}\">", n + 1);
+        let mut at = start;
+        while let Some((range, ty, mods)) = toks.next_if(|(r, _, _)| r.start < end) {
+            if range.start < at {
+                continue;
+            }
+            out.push_str(&escape_html(&doc.text[at..range.start]));
+            let ty = legend.token_types[ty as usize].as_str();
+            let definition = if mods & token_modifiers::DEFINITION != 0 {
+                " definition"
+            } else {
+                ""
+            };
+            let _ = write!(
+                out,
+                "<span class=\"tok-{ty}{definition}\">{}</span>",
+                escape_html(&doc.text[range.start..range.end])
+            );
+            at = range.end;
+        }
+        out.push_str(&escape_html(&doc.text[at..end]));
+        out.push_str("</span>\n");
+        start += line.len();
+    }
+    out.push_str("</code></pre>");
+    out
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 60em; margin: 2em auto; padding: 0 1em; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; vertical-align: top; }
+pre.source { background: #f6f8fa; padding: 1em; }
+pre.source .line:target { background: #fff3b0; }
+.tok-keyword { color: #8959a8; font-weight: bold; }
+.tok-number { color: #f5871f; }
+.tok-string { color: #718c00; }
+.tok-comment { color: #8e908c; font-style: italic; }
+.tok-variable { color: #4271ae; }
+.tok-variable.definition { font-weight: bold; }
+.tok-function { color: #3e999f; }
+.tok-operator { color: #c82829; }
+.tok-parameter { color: #eab700; }";
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{STYLE}\n</style>\n</head>\n<body>\n{body}</body>\n</html>\n",
+        escape_html(title)
+    )
+}
+
+// Doc comments as HTML paragraphs
+fn html_paragraphs(text: &str) -> String {
+    text.split("\n\n")
+        .map(|p| format!("<p>{}</p>", escape_html(p.trim())))
+        .collect()
+}
+
+// Doc comments squeezed into a Markdown table cell
+fn markdown_cell(text: &str) -> String {
+    text.split("\n\n")
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("<br><br>")
+        .replace('|', "\\|")
+}
+
+// The page for one program. `index` links back to the index page.
+pub fn render_page(title: &str, index: &str, doc: &Doc, format: Format) -> String {
+    let labels = label_infos(doc);
+    let summary = file_summary(doc);
+    match format {
+        Format::Html => {
+            let mut body = format!(
+                "<p><a href=\"{}\">Index</a></p>\n<h1>{}</h1>\n",
+                escape_html(index),
+                escape_html(title)
+            );
+            if let Some(summary) = &summary {
+                body.push_str(&html_paragraphs(summary));
+                body.push('\n');
+            }
+            if !labels.is_empty() {
+                body.push_str(
+                    "<h2>Labels</h2>\n<table>\n<tr><th>Label</th><th>Description</th>\
+                     <th>Declared</th><th>Inferred</th><th>Jumps to</th><th>Jumped to from</th></tr>\n",
+                );
+                for l in &labels {
+                    let jumps_to: Vec<String> = l
+                        .jumps_to
+                        .iter()
+                        .map(|t| format!("<a href=\"#label-{0}\">{0}</a>", escape_html(t)))
+                        .collect();
+                    let jumped_from: Vec<String> = l
+                        .jumped_from
+                        .iter()
+                        .map(|n| format!("<a href=\"#L{n}\">line {n}</a>"))
+                        .collect();
+                    let _ = writeln!(
+                        body,
+                        "<tr id=\"label-{name}\"><td><a href=\"#L{line}\"><code>{name}</code></a></td>\
+                         <td>{docs}</td><td>{declared}</td><td><code>{inferred}</code></td>\
+                         <td>{jumps_to}</td><td>{jumped_from}</td></tr>",
+                        name = escape_html(&l.name),
+                        line = l.line,
+                        docs = l.docs.as_deref().map(html_paragraphs).unwrap_or_default(),
+                        declared = l
+                            .declared
+                            .as_deref()
+                            .map(|d| format!("<code>{}</code>", escape_html(d)))
+                            .unwrap_or_default(),
+                        inferred = escape_html(&l.inferred),
+                        jumps_to = jumps_to.join(", "),
+                        jumped_from = jumped_from.join(", "),
+                    );
+                }
+                body.push_str("</table>\n");
+            }
+            body.push_str("<h2>Source</h2>\n");
+            body.push_str(&highlight(doc));
+            body.push('\n');
+            html_page(title, &body)
+        }
+        Format::Markdown => {
+            let mut out = format!("[Index]({index})\n\n# {title}\n\n");
+            if let Some(summary) = &summary {
+                out.push_str(summary);
+                out.push_str("\n\n");
+            }
+            if !labels.is_empty() {
+                out.push_str(
+                    "## Labels\n\n| Label | Description | Declared | Inferred | Jumps to | Jumped to from |\n\
+                     | --- | --- | --- | --- | --- | --- |\n",
+                );
+                for l in &labels {
+                    let jumped_from: Vec<String> =
+                        l.jumped_from.iter().map(|n| format!("line {n}")).collect();
+                    let code = |s: &str| {
+                        if s.is_empty() {
+                            String::new()
+                        } else {
+                            format!("`{s}`")
+                        }
+                    };
+                    let _ = writeln!(
+                        out,
+                        "| `{}` (line {}) | {} | {} | {} | {} | {} |",
+                        l.name,
+                        l.line,
+                        l.docs.as_deref().map(markdown_cell).unwrap_or_default(),
+                        code(l.declared.as_deref().unwrap_or("")),
+                        code(&l.inferred),
+                        l.jumps_to
+                            .iter()
+                            .map(|t| code(t))
+                            .collect::<Vec<_>>()
+                            .join(", "),
+                        jumped_from.join(", "),
+                    );
+                }
+                out.push('\n');
+            }
+            // Markdown has no way to say how to colour a token, so this is left to the
+            // renderer's highlighting of `froggy` blocks
+            let _ = write!(out, "## Source\n\n```froggy\n{}", doc.text);
+            if !doc.text.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str("```\n");
+            out
+        }
+    }
+}
+
+// An entry of the index page: the page's path relative to the index, its title and
+// the summary of the file
+pub struct IndexEntry {
+    pub link: String,
+    pub title: String,
+    pub summary: Option<String>,
+}
+
+pub fn render_index(title: &str, entries: &[IndexEntry], format: Format) -> String {
+    match format {
+        Format::Html => {
+            let mut body = format!("<h1>{}</h1>\n<ul>\n", escape_html(title));
+            for e in entries {
+                let summary = e
+                    .summary
+                    .as_deref()
+                    .and_then(|s| s.lines().next())
+                    .map(|s| format!(": {}", escape_html(s)))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    body,
+                    "<li><a href=\"{}\">{}</a>{summary}</li>",
+                    escape_html(&e.link),
+                    escape_html(&e.title)
+                );
+            }
+            body.push_str("</ul>\n");
+            html_page(title, &body)
+        }
+        Format::Markdown => {
+            let mut out = format!("# {title}\n\n");
+            for e in entries {
+                let summary = e
+                    .summary
+                    .as_deref()
+                    .and_then(|s| s.lines().next())
+                    .map(|s| format!(": {s}"))
+                    .unwrap_or_default();
+                let _ = writeln!(out, "- [{}]({}){summary}", e.title, e.link);
+            }
+            out
+        }
+    }
+}
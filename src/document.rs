@@ -1,40 +1,44 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-use crate::utils::tree_sitter_helpers::dfs_visit;
+use crate::analysis::Queries;
+use crate::analysis::stack::StackEffect;
+use crate::utils::tree_sitter_helpers::{dfs_visit, node_byte_range};
 use line_index::{LineIndex, TextSize, WideLineCol};
 use tower_lsp::lsp_types::Position;
-use tree_sitter::{Parser, Tree};
+use tree_sitter::{LanguageError, Node, ParseOptions, ParseState, Parser, Tree};
+
+// Longest a single parse may run before it is abandoned
+pub const PARSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug)]
 pub struct Doc {
     pub text: String,
     pub version: i32,
     pub tree: Tree,
-    pub index: Index,
     pub line_index: LineIndex,
+    pub(crate) queries: Queries,
 }
 
 impl Doc {
     pub fn new(text: String, version: i32, tree: Tree) -> Self {
         let line_index = LineIndex::new(&text);
-        let index = Index::build(&tree, &text);
         Self {
             text,
             version,
             tree,
-            index,
             line_index,
+            queries: Queries::default(),
         }
     }
 
-    pub fn update(&mut self, text: String, version: i32, parser: &mut Parser) {
-        self.text = text;
-        self.version = version;
-        self.tree = parser
-            .parse(&self.text, None)
-            .expect("parse() returned None");
-        self.index = Index::build(&self.tree, &self.text);
-        self.line_index = LineIndex::new(&self.text);
+    // Parse `text` into a new document, giving up early if `cancel` is set
+    pub fn parse(text: String, version: i32, cancel: &AtomicBool) -> Result<Self, ParseError> {
+        let tree = parse_text(&text, cancel)?;
+        Ok(Self::new(text, version, tree))
     }
 
     // Convert LSP position (UTF-16) to byte offset
@@ -51,7 +55,7 @@ impl Doc {
 
     // Convert byte offset to LSP position (UTF-16)
     pub fn offset_to_lsp_position(&self, offset: usize) -> Option<Position> {
-        let text_size = TextSize::try_from(offset as u32).ok()?;
+        let text_size = TextSize::from(offset as u32);
         let line_col = self.line_index.line_col(text_size);
         let wide_line_col = self
             .line_index
@@ -64,17 +68,76 @@ impl Doc {
     }
 }
 
-pub fn make_parser() -> Parser {
+#[derive(Debug)]
+pub enum ParseError {
+    Language(LanguageError),
+    TimedOut,
+    Cancelled,
+    NoTree,
+    Panicked,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Language(e) => write!(f, "the Froggy grammar could not be loaded: {e}"),
+            Self::TimedOut => write!(f, "parsing timed out after {}s", PARSE_TIMEOUT.as_secs()),
+            Self::Cancelled => write!(f, "parsing was cancelled"),
+            Self::NoTree => write!(f, "the parser returned no tree"),
+            Self::Panicked => write!(f, "the parser panicked"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn make_parser() -> Result<Parser, ParseError> {
     let mut p = Parser::new();
     p.set_language(&tree_sitter_froggy::LANGUAGE.into())
-        .expect("load language");
-    p
+        .map_err(ParseError::Language)?;
+    Ok(p)
+}
+
+// Parse `text`, giving up after PARSE_TIMEOUT or as soon as `cancel` is set
+pub fn parse_text(text: &str, cancel: &AtomicBool) -> Result<Tree, ParseError> {
+    let mut parser = make_parser()?;
+    let deadline = Instant::now() + PARSE_TIMEOUT;
+    let mut timed_out = false;
+
+    let mut progress = |_: &ParseState| {
+        if cancel.load(Ordering::Relaxed) {
+            return ControlFlow::Break(());
+        }
+        if Instant::now() >= deadline {
+            timed_out = true;
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    };
+
+    let bytes = text.as_bytes();
+    let tree = parser.parse_with_options(
+        &mut |i, _| bytes.get(i..).unwrap_or_default(),
+        None,
+        Some(ParseOptions::new().progress_callback(&mut progress)),
+    );
+
+    match tree {
+        Some(tree) => Ok(tree),
+        None if timed_out => Err(ParseError::TimedOut),
+        None if cancel.load(Ordering::Relaxed) => Err(ParseError::Cancelled),
+        None => Err(ParseError::NoTree),
+    }
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct Index {
     pub label_defs: HashMap<String, ByteRange>,
     pub label_refs: HashMap<String, Vec<ByteRange>>,
+    // Declared stack effects, by the label they are annotated on
+    pub stack_effects: HashMap<String, StackEffect>,
+    // Markdown from the comments above a label's definition
+    pub label_docs: HashMap<String, String>,
 }
 
 impl Index {
@@ -85,30 +148,43 @@ impl Index {
         dfs_visit(tree, |node| match node.kind() {
             "label_definition" => {
                 let id = node.child_by_field_name("name");
-                if let Some(id) = id {
-                    if let Ok(name) = id.utf8_text(bytes) {
-                        idx.label_defs.insert(
-                            name.to_string(),
-                            ByteRange {
-                                start: node.start_byte(),
-                                end: node.end_byte(),
-                            },
-                        );
-                    }
+                if let Some(id) = id
+                    && let Ok(name) = id.utf8_text(bytes)
+                {
+                    idx.label_defs.insert(
+                        name.to_string(),
+                        ByteRange {
+                            start: node.start_byte(),
+                            end: node.end_byte(),
+                        },
+                    );
+                    // Like the definition, the last annotation of a duplicate label wins
+                    let comments = comments_above(node);
+                    let effect = comments.iter().rev().find_map(|c| {
+                        StackEffect::parse(c.utf8_text(bytes).ok()?, node_byte_range(*c))
+                    });
+                    match effect {
+                        Some(effect) => idx.stack_effects.insert(name.to_string(), effect),
+                        None => idx.stack_effects.remove(name),
+                    };
+                    match doc_comment(&comments, bytes) {
+                        Some(docs) => idx.label_docs.insert(name.to_string(), docs),
+                        None => idx.label_docs.remove(name),
+                    };
                 }
             }
             "hop" | "leap" => {
                 let id = node.child_by_field_name("target");
-                if let Some(id) = id {
-                    if let Ok(name) = id.utf8_text(bytes) {
-                        idx.label_refs
-                            .entry(name.to_string())
-                            .or_default()
-                            .push(ByteRange {
-                                start: id.start_byte(),
-                                end: id.end_byte(),
-                            });
-                    }
+                if let Some(id) = id
+                    && let Ok(name) = id.utf8_text(bytes)
+                {
+                    idx.label_refs
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(ByteRange {
+                            start: id.start_byte(),
+                            end: id.end_byte(),
+                        });
                 }
             }
             _ => {}
@@ -118,7 +194,46 @@ impl Index {
     }
 }
 
-#[derive(Clone, Debug)]
+// Comments on the lines directly above the statement holding `node`, in source order.
+// A comment trailing an instruction belongs to that instruction, so it ends the run.
+fn comments_above(node: Node) -> Vec<Node> {
+    let mut out = Vec::new();
+    let mut cur = node
+        .parent()
+        .filter(|p| p.kind() == "statement")
+        .unwrap_or(node);
+    while let Some(prev) = cur.prev_sibling()
+        && prev.kind() == "comment"
+        && prev.end_position().row + 1 == cur.start_position().row
+        && prev
+            .prev_sibling()
+            .is_none_or(|p| p.end_position().row < prev.start_position().row)
+    {
+        out.push(prev);
+        cur = prev;
+    }
+    out.reverse();
+    out
+}
+
+// Text of a run of doc comments, minus the `//`s, stack-effect annotations and
+// `froggy-ignore` directives; None if that leaves nothing
+pub(crate) fn doc_comment(comments: &[Node], bytes: &[u8]) -> Option<String> {
+    let lines: Vec<&str> = comments
+        .iter()
+        .filter_map(|&c| {
+            let text = c.utf8_text(bytes).ok()?;
+            let body = text.strip_prefix("//")?;
+            let directive = body.trim_start().starts_with("froggy-ignore")
+                || StackEffect::parse(text, node_byte_range(c)).is_some();
+            (!directive).then(|| body.strip_prefix(' ').unwrap_or(body).trim_end())
+        })
+        .collect();
+    let docs = lines.join("\n").trim().to_string();
+    (!docs.is_empty()).then_some(docs)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct ByteRange {
     pub start: usize,
     pub end: usize,
@@ -0,0 +1,156 @@
+use tower_lsp::lsp_types::{Hover, Position};
+
+use crate::document::{ByteRange, Doc};
+use crate::utils::froggy_helpers::{
+    find_label_definition, leading_word_range, make_hover, make_markdown_hover,
+};
+use crate::utils::tree_sitter_helpers::find_node_at_position;
+
+// Describe the instruction or label under `position`
+pub fn hover_at(doc: &Doc, position: Position) -> Option<Hover> {
+    let node = find_node_at_position(&doc.tree, doc, position);
+    let bytes = doc.text.as_bytes();
+
+    let mut cur = node;
+
+    loop {
+        let r = leading_word_range(&doc.text, cur);
+        match cur.kind() {
+            // Stack operations (both the rule names and string literals)
+            "PLOP" | "plop" => {
+                return Some(make_hover(
+                    "PLOP <value>: Push a value onto the stack",
+                    r,
+                    doc,
+                ));
+            }
+            "SPLASH" | "splash" => {
+                return Some(make_hover("SPLASH: Pop a value off the stack", r, doc));
+            }
+            "GULP" | "gulp" => {
+                return Some(make_hover("GULP: Increment top of stack", r, doc));
+            }
+            "BURP" | "burp" => {
+                return Some(make_hover("BURP: Decrement top of stack", r, doc));
+            }
+            "DUP" | "dup" => {
+                return Some(make_hover("DUP: Duplicate top of stack", r, doc));
+            }
+            "SWAP" | "swap" => {
+                return Some(make_hover("SWAP: Swap top two stack values", r, doc));
+            }
+            "OVER" | "over" => {
+                return Some(make_hover(
+                    "OVER: Duplicate second from top of stack",
+                    r,
+                    doc,
+                ));
+            }
+
+            // Control flow (both rule names and string literals)
+            "LILY" | "lily" => {
+                return Some(make_hover("LILY <label>: Define a lilypad label", r, doc));
+            }
+            "HOP" | "hop" => {
+                return Some(make_hover(
+                    "HOP <Lilypad>: Unconditional jump to a lilypad",
+                    r,
+                    doc,
+                ));
+            }
+            "LEAP" | "leap" => {
+                return Some(make_hover(
+                    "LEAP <Lilypad>: Pop a, if (a == 0) then jump to lilypad",
+                    r,
+                    doc,
+                ));
+            }
+
+            // Label definition
+            "label_definition" => {
+                if let Some(label_node) = cur.child_by_field_name("name") {
+                    let label_text = label_node.utf8_text(bytes).unwrap_or("");
+                    let label_range = ByteRange {
+                        start: label_node.start_byte(),
+                        end: label_node.end_byte(),
+                    };
+                    return Some(label_hover(
+                        doc,
+                        "Label definition",
+                        label_text,
+                        label_range,
+                    ));
+                }
+            }
+
+            // IO (both rule names and string literals)
+            "RIBBIT" | "ribbit" => {
+                return Some(make_hover("RIBBIT: Print top of stack", r, doc));
+            }
+            "CROAK" | "croak" => return Some(make_hover("CROAK: Read input", r, doc)),
+
+            // Arithmetic (both rule names and string literals)
+            "ADD" | "add" => return Some(make_hover("ADD: Pop a b, push (b + a)", r, doc)),
+            "SUB" | "sub" => return Some(make_hover("SUB: Pop a b, push (b - a)", r, doc)),
+            "MUL" | "mul" => return Some(make_hover("MUL: Pop a b, push (b * a)", r, doc)),
+            "DIV" | "div" => return Some(make_hover("DIV: Pop a b, push (b / a)", r, doc)),
+
+            // Comparison (both rule names and string literals)
+            "EQUALS" | "equals" => {
+                return Some(make_hover("EQUALS: Pop a b, push (b == a)", r, doc));
+            }
+            "NOT_EQUAL" | "not_equal" => {
+                return Some(make_hover("NOT_EQUAL: Pop a b, push (b != a)", r, doc));
+            }
+            "LESS_THAN" | "less_than" => {
+                return Some(make_hover("LESS_THAN: Pop a b, push (b < a)", r, doc));
+            }
+            "GREATER_THAN" | "greater_than" => {
+                return Some(make_hover("GREATER_THAN: Pop a b, push (b > a)", r, doc));
+            }
+            "LESS_EQ" | "less_eq" => {
+                return Some(make_hover("LESS_EQ: Pop a b, push (b <= a)", r, doc));
+            }
+            "GREATER_EQ" | "greater_eq" => {
+                return Some(make_hover("GREATER_EQ: Pop a b, push (b >= a)", r, doc));
+            }
+            "identifier" => {
+                if let Some(parent) = cur.parent() {
+                    let text = cur.utf8_text(bytes).unwrap_or("");
+                    let documented = doc.index().stack_effects.contains_key(text)
+                        || doc.index().label_docs.contains_key(text);
+                    if matches!(parent.kind(), "hop" | "leap") && documented {
+                        // What the target does matters more than the mnemonic
+                        return Some(label_hover(doc, "Label", text, r));
+                    } else if matches!(parent.kind(), "label_definition" | "hop" | "leap") {
+                        // Continue to parent instead of returning
+                    } else if let Some(_def) = find_label_definition(doc.index(), text) {
+                        return Some(label_hover(doc, "Label", text, r));
+                    }
+                }
+            }
+
+            _ => {}
+        }
+        // Break out of loop if node is parentless
+        match cur.parent() {
+            Some(p) => cur = p,
+            None => break,
+        }
+    }
+
+    None
+}
+
+// `what: name` and the label's declared stack effect, then its doc comment if it has one
+fn label_hover(doc: &Doc, what: &str, name: &str, range: ByteRange) -> Hover {
+    let index = doc.index();
+    let summary = match index.stack_effects.get(name) {
+        Some(effect) => format!("{what}: {name} {effect}"),
+        None => format!("{what}: {name}"),
+    };
+    match index.label_docs.get(name) {
+        Some(docs) => make_markdown_hover(&format!("{summary}\n\n{docs}"), range, doc),
+        None => make_hover(&summary, range, doc),
+    }
+}
@@ -0,0 +1,353 @@
+// An interpreter that is fed a program piece by piece, as the REPL does. Jumps are
+// resolved by label name when they are taken, so a jump to a label that a later piece
+// defines waits for it; the instructions entered in between are skipped, as they
+// would be in a whole program.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::analysis::stack::stack_effect;
+use crate::bytecode::Instr;
+use crate::compiler::{CompileError, lower};
+use crate::document::Doc;
+use crate::utils::froggy_helpers::program_instructions;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    // A line read by CROAK: a number if it looks like one
+    pub fn parse(input: &str) -> Self {
+        let input = input.trim();
+        if let Ok(n) = input.parse() {
+            return Self::Int(n);
+        }
+        match input.parse() {
+            Ok(f) if input.contains(|c: char| c.is_ascii_digit()) => Self::Float(f),
+            _ => Self::Str(input.to_string()),
+        }
+    }
+
+    fn number(&self) -> Option<f64> {
+        match self {
+            Self::Int(n) => Some(*n as f64),
+            Self::Float(f) => Some(*f),
+            Self::Str(_) => None,
+        }
+    }
+}
+
+// As RIBBIT prints it
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(n) => write!(f, "{n}"),
+            Self::Float(x) => write!(f, "{x:?}"),
+            Self::Str(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+// Where RIBBIT prints to and CROAK reads from
+pub trait Io {
+    fn print(&mut self, value: &Value);
+    // A line of input; None at the end of input
+    fn read(&mut self) -> Option<String>;
+}
+
+#[derive(Debug)]
+pub struct RuntimeError {
+    pub message: String,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+fn error(message: impl Into<String>) -> RuntimeError {
+    RuntimeError {
+        message: message.into(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Status {
+    // Everything entered so far has run
+    Done,
+    // Instructions are left to run
+    Ready,
+    // Stopped at a jump to a label that isn't defined yet
+    Waiting(String),
+}
+
+struct Step {
+    instr: Instr,
+    kind: &'static str,
+    // Label a HOP or LEAP goes to
+    target: Option<String>,
+    // The instruction as written
+    source: String,
+}
+
+#[derive(Default)]
+pub struct Interpreter {
+    code: Vec<Step>,
+    // Index into `code` of the instruction after each label
+    labels: HashMap<String, usize>,
+    strings: Vec<String>,
+    stack: Vec<Value>,
+    pc: usize,
+    waiting: Option<String>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.stack
+    }
+
+    pub fn status(&self) -> Status {
+        match &self.waiting {
+            Some(label) => Status::Waiting(label.clone()),
+            None if self.pc < self.code.len() => Status::Ready,
+            None => Status::Done,
+        }
+    }
+
+    // Source of the instruction that runs next
+    pub fn next_instruction(&self) -> Option<&str> {
+        match self.status() {
+            Status::Ready => Some(&self.code[self.pc].source),
+            _ => None,
+        }
+    }
+
+    // Append a piece of program without syntax errors. Its labels are defined at once,
+    // like the last definition of a label is the one jumps go to in a whole program.
+    pub fn load(&mut self, doc: &Doc) -> Result<(), Vec<CompileError>> {
+        let bytes = doc.text.as_bytes();
+        let mut steps = Vec::new();
+        let mut labels = Vec::new();
+        let mut errors = Vec::new();
+
+        for node in program_instructions(&doc.tree) {
+            let text = |field| {
+                let node = node.child_by_field_name(field)?;
+                node.utf8_text(bytes).ok().map(str::to_string)
+            };
+            if node.kind() == "label_definition" {
+                if let Some(name) = text("name") {
+                    labels.push((name, self.code.len() + steps.len()));
+                }
+                continue;
+            }
+            match lower(node, doc, &mut self.strings) {
+                Ok(instr) => steps.push(Step {
+                    instr,
+                    kind: node.kind(),
+                    target: text("target"),
+                    source: node.utf8_text(bytes).unwrap_or("").to_string(),
+                }),
+                Err(e) => errors.push(e),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.code.extend(steps);
+        self.labels.extend(labels);
+        if let Some(label) = &self.waiting
+            && let Some(&at) = self.labels.get(label)
+        {
+            self.pc = at;
+            self.waiting = None;
+        }
+        Ok(())
+    }
+
+    // Run until everything has run, a jump waits for its label, or `budget` instructions
+    // have run (a loop may never end)
+    pub fn run(&mut self, budget: usize, io: &mut impl Io) -> Result<Status, RuntimeError> {
+        for _ in 0..budget {
+            if self.status() != Status::Ready {
+                break;
+            }
+            self.step(io)?;
+        }
+        Ok(self.status())
+    }
+
+    // Run the next instruction. After an error the rest of what was entered is skipped,
+    // and the stack is as it was before the failing instruction.
+    pub fn step(&mut self, io: &mut impl Io) -> Result<(), RuntimeError> {
+        if self.status() != Status::Ready {
+            return Ok(());
+        }
+        let result = self.execute(io);
+        if result.is_err() {
+            self.pc = self.code.len();
+        }
+        result
+    }
+
+    fn execute(&mut self, io: &mut impl Io) -> Result<(), RuntimeError> {
+        let step = &self.code[self.pc];
+        let (instr, kind, target) = (step.instr, step.kind, step.target.clone());
+        let mnemonic = kind.to_ascii_uppercase();
+        self.pc += 1;
+
+        let (pops, _) = stack_effect(kind);
+        let depth = self.stack.len();
+        if depth < pops as usize {
+            let values = if pops == 1 { "value" } else { "values" };
+            return Err(error(format!(
+                "`{mnemonic}` needs {pops} {values}, but the stack has {depth}"
+            )));
+        }
+
+        match instr {
+            Instr::PushInt(n) => self.stack.push(Value::Int(n)),
+            Instr::PushFloat(x) => self.stack.push(Value::Float(x)),
+            Instr::PushStr(i) => self
+                .stack
+                .push(Value::Str(self.strings[i as usize].clone())),
+            Instr::Pop => {
+                self.stack.pop();
+            }
+            Instr::Inc | Instr::Dec => {
+                let op = if instr == Instr::Inc {
+                    Instr::Add
+                } else {
+                    Instr::Sub
+                };
+                let top = self.stack.last_mut().unwrap();
+                *top = arithmetic(&mnemonic, op, top, &Value::Int(1))?;
+            }
+            Instr::Dup => self.stack.push(self.stack[depth - 1].clone()),
+            Instr::Swap => self.stack.swap(depth - 1, depth - 2),
+            Instr::Over => self.stack.push(self.stack[depth - 2].clone()),
+            Instr::Print => io.print(&self.stack[depth - 1]),
+            Instr::Read => {
+                let line = io
+                    .read()
+                    .ok_or_else(|| error("`CROAK` found no more input"))?;
+                self.stack.push(Value::parse(&line));
+            }
+            Instr::Add | Instr::Sub | Instr::Mul | Instr::Div => {
+                let value = arithmetic(
+                    &mnemonic,
+                    instr,
+                    &self.stack[depth - 2],
+                    &self.stack[depth - 1],
+                )?;
+                self.stack.truncate(depth - 2);
+                self.stack.push(value);
+            }
+            Instr::Eq | Instr::Ne | Instr::Lt | Instr::Gt | Instr::Le | Instr::Ge => {
+                let holds = compare(
+                    &mnemonic,
+                    instr,
+                    &self.stack[depth - 2],
+                    &self.stack[depth - 1],
+                )?;
+                self.stack.truncate(depth - 2);
+                self.stack.push(Value::Int(holds.into()));
+            }
+            Instr::Jump(_) => self.jump(target),
+            Instr::JumpIfZero(_) => {
+                let zero = self.stack[depth - 1].number().ok_or_else(|| {
+                    error(format!("`{mnemonic}` needs a number on top of the stack"))
+                })? == 0.0;
+                self.stack.pop();
+                if zero {
+                    self.jump(target);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn jump(&mut self, target: Option<String>) {
+        let Some(target) = target else {
+            return;
+        };
+        match self.labels.get(&target) {
+            Some(&at) => self.pc = at,
+            None => self.waiting = Some(target),
+        }
+    }
+}
+
+// `b op a`, where `a` was on top of the stack. Integers stay integers; strings can
+// only be joined.
+fn arithmetic(mnemonic: &str, op: Instr, b: &Value, a: &Value) -> Result<Value, RuntimeError> {
+    match (b, a) {
+        (Value::Int(b), Value::Int(a)) => {
+            let result = match op {
+                Instr::Add => b.checked_add(*a),
+                Instr::Sub => b.checked_sub(*a),
+                Instr::Mul => b.checked_mul(*a),
+                _ if *a == 0 => return Err(error("Division by zero")),
+                _ => b.checked_div(*a),
+            };
+            result
+                .map(Value::Int)
+                .ok_or_else(|| error(format!("`{mnemonic}` overflowed 64 bits")))
+        }
+        (Value::Str(b), Value::Str(a)) if op == Instr::Add => Ok(Value::Str(format!("{b}{a}"))),
+        _ => {
+            let (Some(b), Some(a)) = (b.number(), a.number()) else {
+                return Err(error(format!("`{mnemonic}` cannot be applied to a string")));
+            };
+            Ok(Value::Float(match op {
+                Instr::Add => b + a,
+                Instr::Sub => b - a,
+                Instr::Mul => b * a,
+                _ if a == 0.0 => return Err(error("Division by zero")),
+                _ => b / a,
+            }))
+        }
+    }
+}
+
+// Whether `b op a` holds. Strings are only ordered among themselves, and never equal
+// to a number.
+fn compare(mnemonic: &str, op: Instr, b: &Value, a: &Value) -> Result<bool, RuntimeError> {
+    let ordering = match (b, a) {
+        (Value::Int(b), Value::Int(a)) => Some(b.cmp(a)),
+        (Value::Str(b), Value::Str(a)) => Some(b.cmp(a)),
+        _ => match (b.number(), a.number()) {
+            (Some(b), Some(a)) => b.partial_cmp(&a),
+            _ if matches!(op, Instr::Eq | Instr::Ne) => return Ok(op == Instr::Ne),
+            _ => {
+                return Err(error(format!(
+                    "`{mnemonic}` cannot compare a string with a number"
+                )));
+            }
+        },
+    };
+    // NaN compares false, except for inequality
+    let Some(ordering) = ordering else {
+        return Ok(op == Instr::Ne);
+    };
+    Ok(match op {
+        Instr::Eq => ordering.is_eq(),
+        Instr::Ne => ordering.is_ne(),
+        Instr::Lt => ordering.is_lt(),
+        Instr::Gt => ordering.is_gt(),
+        Instr::Le => ordering.is_le(),
+        _ => ordering.is_ge(),
+    })
+}
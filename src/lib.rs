@@ -0,0 +1,23 @@
+pub mod analysis;
+pub mod backend;
+pub mod bytecode;
+pub mod code_actions;
+pub mod compiler;
+pub mod completion;
+pub mod diagnostics;
+pub mod docgen;
+pub mod document;
+pub mod hover;
+pub mod interpreter;
+pub mod lint;
+pub mod logging;
+pub mod markdown;
+pub mod notebook;
+pub mod project;
+pub mod selection_range;
+pub mod semantic_tokens;
+pub mod settings;
+pub mod store;
+pub mod syntax;
+pub mod transport;
+pub mod utils;
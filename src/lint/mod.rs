@@ -0,0 +1,211 @@
+mod rules;
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use tower_lsp::lsp_types::*;
+
+use crate::document::{ByteRange, Doc};
+use crate::utils::tree_sitter_helpers::labeldef_to_range;
+
+pub struct Rule {
+    pub id: &'static str,
+    pub default_severity: DiagnosticSeverity,
+    pub description: &'static str,
+    check: fn(&Doc, &LintConfig, &mut Vec<Finding>),
+}
+
+// A single rule violation, before severity is applied
+pub struct Finding {
+    pub range: ByteRange,
+    pub message: String,
+    pub tags: Vec<DiagnosticTag>,
+}
+
+impl Finding {
+    pub fn new(range: ByteRange, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            message: message.into(),
+            tags: Vec::new(),
+        }
+    }
+
+    pub fn unnecessary(mut self) -> Self {
+        self.tags.push(DiagnosticTag::UNNECESSARY);
+        self
+    }
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "unused-label",
+        default_severity: DiagnosticSeverity::HINT,
+        description: "Label is fallen into but never targeted by HOP or LEAP",
+        check: rules::unused_label,
+    },
+    Rule {
+        id: "unentered-label",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "Label is never jumped to and cannot be fallen into",
+        check: rules::unentered_label,
+    },
+    Rule {
+        id: "label-shadowing",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "Label is defined more than once; jumps go to the last definition",
+        check: rules::label_shadowing,
+    },
+    Rule {
+        id: "mixed-case",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "Mnemonic is not written in upper case",
+        check: rules::mixed_case,
+    },
+    Rule {
+        id: "redundant-stack-op",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "`DUP SPLASH` or `SWAP SWAP` leaves the stack unchanged",
+        check: rules::redundant_stack_op,
+    },
+    Rule {
+        id: "gulp-burp-noop",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "`GULP BURP` or `BURP GULP` leaves the stack unchanged",
+        check: rules::gulp_burp_noop,
+    },
+    Rule {
+        id: "unreachable-code",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "Instructions after HOP that no label makes reachable",
+        check: rules::unreachable_code,
+    },
+    Rule {
+        id: "jump-to-next-line",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "HOP or LEAP targets the label on the very next line",
+        check: rules::jump_to_next_line,
+    },
+    Rule {
+        id: "stack-effect-mismatch",
+        default_severity: DiagnosticSeverity::WARNING,
+        description: "Block does not have the stack effect its `( -- )` annotation declares",
+        check: rules::stack_effect_mismatch,
+    },
+];
+
+pub fn find_rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.id == id)
+}
+
+// Severity a rule is configured to, or disabled entirely
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum RuleLevel {
+    Off,
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+impl RuleLevel {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" | "none" | "allow" => Some(Self::Off),
+            "hint" => Some(Self::Hint),
+            "info" | "information" => Some(Self::Info),
+            "warn" | "warning" => Some(Self::Warning),
+            "error" | "deny" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    fn severity(self) -> Option<DiagnosticSeverity> {
+        match self {
+            Self::Off => None,
+            Self::Hint => Some(DiagnosticSeverity::HINT),
+            Self::Info => Some(DiagnosticSeverity::INFORMATION),
+            Self::Warning => Some(DiagnosticSeverity::WARNING),
+            Self::Error => Some(DiagnosticSeverity::ERROR),
+        }
+    }
+}
+
+impl TryFrom<String> for RuleLevel {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::parse(&s).ok_or_else(|| format!("invalid lint level `{s}`"))
+    }
+}
+
+// Per-workspace rule overrides; rules not listed run at their default severity
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(from = "HashMap<String, RuleLevel>")]
+pub struct LintConfig {
+    pub overrides: HashMap<String, RuleLevel>,
+    // Labels entered from outside the file, so never reported as unused
+    pub entry_points: Vec<String>,
+}
+
+impl From<HashMap<String, RuleLevel>> for LintConfig {
+    fn from(overrides: HashMap<String, RuleLevel>) -> Self {
+        Self {
+            overrides,
+            entry_points: Vec::new(),
+        }
+    }
+}
+
+impl LintConfig {
+    // Layer `other` on top of self, rule by rule
+    pub fn merged(&self, other: &LintConfig) -> LintConfig {
+        let mut merged = self.clone();
+        merged.overrides.extend(other.overrides.clone());
+        merged
+            .entry_points
+            .extend(other.entry_points.iter().cloned());
+        merged
+    }
+
+    // Rule ids that don't name a known rule
+    pub fn unknown_rules(&self) -> impl Iterator<Item = &str> {
+        self.overrides
+            .keys()
+            .map(String::as_str)
+            .filter(|id| find_rule(id).is_none())
+    }
+
+    pub fn severity(&self, rule: &Rule) -> Option<DiagnosticSeverity> {
+        match self.overrides.get(rule.id) {
+            Some(level) => level.severity(),
+            None => Some(rule.default_severity),
+        }
+    }
+}
+
+pub fn run_lints(doc: &Doc, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+
+    for rule in RULES {
+        let Some(severity) = config.severity(rule) else {
+            continue;
+        };
+
+        let mut findings = Vec::new();
+        (rule.check)(doc, config, &mut findings);
+
+        out.extend(findings.into_iter().map(|f| Diagnostic {
+            range: labeldef_to_range(&f.range, doc),
+            severity: Some(severity),
+            code: Some(NumberOrString::String(rule.id.to_string())),
+            source: Some("froggy".to_string()),
+            message: f.message,
+            tags: (!f.tags.is_empty()).then_some(f.tags),
+            ..Default::default()
+        }));
+    }
+
+    out
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use tree_sitter::Node;
+
+use super::{Finding, LintConfig};
+use crate::document::{ByteRange, Doc};
+use crate::syntax::syntax_errors;
+use crate::utils::froggy_helpers::{MNEMONICS, label_names, program_instructions};
+use crate::utils::tree_sitter_helpers::{dfs_visit, node_byte_range};
+
+fn label_name<'a>(node: Node, doc: &'a Doc) -> Option<&'a str> {
+    let field = match node.kind() {
+        "label_definition" => "name",
+        "hop" | "leap" => "target",
+        _ => return None,
+    };
+    node.child_by_field_name(field)?
+        .utf8_text(doc.text.as_bytes())
+        .ok()
+}
+
+fn span(first: Node, last: Node) -> ByteRange {
+    ByteRange {
+        start: first.start_byte(),
+        end: last.end_byte(),
+    }
+}
+
+fn is_entry_point(config: &LintConfig, name: &str) -> bool {
+    config.entry_points.iter().any(|e| e == name)
+}
+
+// For each top-level instruction, whether control can reach it by falling through,
+// a jump or an entry point. Jumps from unreachable code still count as entering a label.
+fn fall_through_reachable(doc: &Doc, config: &LintConfig, instrs: &[Node]) -> Vec<bool> {
+    let mut reachable = Vec::with_capacity(instrs.len());
+    for (i, node) in instrs.iter().enumerate() {
+        let fallen_into = match i.checked_sub(1) {
+            None => true,
+            Some(prev) => instrs[prev].kind() != "hop" && reachable[prev],
+        };
+        let entered = fallen_into
+            || (node.kind() == "label_definition"
+                && label_name(*node, doc).is_some_and(|n| {
+                    doc.index().label_refs.contains_key(n) || is_entry_point(config, n)
+                }));
+        reachable.push(entered);
+    }
+    reachable
+}
+
+pub fn unused_label(doc: &Doc, config: &LintConfig, out: &mut Vec<Finding>) {
+    let instrs = program_instructions(&doc.tree);
+    let reachable = fall_through_reachable(doc, config, &instrs);
+
+    for (node, reachable) in instrs.iter().zip(reachable) {
+        if node.kind() != "label_definition" || !reachable {
+            continue;
+        }
+        if let Some(name) = label_name(*node, doc)
+            && !doc.index().label_refs.contains_key(name)
+            && !is_entry_point(config, name)
+        {
+            out.push(
+                Finding::new(
+                    node_byte_range(*node),
+                    format!("Label `{name}` is never jumped to"),
+                )
+                .unnecessary(),
+            );
+        }
+    }
+}
+
+pub fn unentered_label(doc: &Doc, config: &LintConfig, out: &mut Vec<Finding>) {
+    let instrs = program_instructions(&doc.tree);
+    let reachable = fall_through_reachable(doc, config, &instrs);
+
+    for (node, reachable) in instrs.iter().zip(reachable) {
+        if node.kind() == "label_definition"
+            && !reachable
+            && let Some(name) = label_name(*node, doc)
+        {
+            out.push(
+                Finding::new(
+                    node_byte_range(*node),
+                    format!("Label `{name}` is never jumped to and cannot be fallen into"),
+                )
+                .unnecessary(),
+            );
+        }
+    }
+}
+
+pub fn label_shadowing(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+
+    for node in program_instructions(&doc.tree) {
+        if node.kind() != "label_definition" {
+            continue;
+        }
+        let Some(name) = label_name(node, doc) else {
+            continue;
+        };
+        if let Some(&prev) = seen.get(name) {
+            let line = doc
+                .offset_to_lsp_position(prev)
+                .map(|p| p.line + 1)
+                .unwrap_or_default();
+            out.push(Finding::new(
+                node_byte_range(node),
+                format!("Label `{name}` shadows the definition on line {line}"),
+            ));
+        }
+        seen.insert(name, node.start_byte());
+    }
+}
+
+pub fn mixed_case(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    // A mnemonic a syntax error already points at isn't reported twice
+    let reported: Vec<ByteRange> = syntax_errors(&doc.tree, &doc.text)
+        .into_iter()
+        .map(|e| e.range)
+        .collect();
+    dfs_visit(&doc.tree, |node| {
+        // Lower/mixed case mnemonics don't lex, so they surface as ERROR nodes
+        if !node.is_error() || node.parent().is_some_and(|p| p.is_error()) {
+            return;
+        }
+        let Ok(text) = node.utf8_text(doc.text.as_bytes()) else {
+            return;
+        };
+        let mut offset = node.start_byte();
+        for word in text.split_inclusive(char::is_whitespace) {
+            let trimmed = word.trim_end();
+            let upper = trimmed.to_ascii_uppercase();
+            let range = ByteRange {
+                start: offset,
+                end: offset + trimmed.len(),
+            };
+            if trimmed != upper
+                && MNEMONICS.contains(&upper.as_str())
+                && !reported
+                    .iter()
+                    .any(|r| r.start < range.end && range.start < r.end)
+            {
+                out.push(Finding::new(
+                    range,
+                    format!("Mnemonic `{trimmed}` must be written as `{upper}`"),
+                ));
+            }
+            offset += word.len();
+        }
+    });
+}
+
+// Report each adjacent pair of instructions matching one of `pairs`
+fn adjacent_pairs(doc: &Doc, out: &mut Vec<Finding>, pairs: &[(&str, &str)]) {
+    let instrs = program_instructions(&doc.tree);
+    let mut i = 0;
+    while i + 1 < instrs.len() {
+        let (a, b) = (instrs[i], instrs[i + 1]);
+        if pairs.contains(&(a.kind(), b.kind())) {
+            out.push(
+                Finding::new(
+                    span(a, b),
+                    format!(
+                        "`{} {}` has no effect",
+                        a.kind().to_ascii_uppercase(),
+                        b.kind().to_ascii_uppercase()
+                    ),
+                )
+                .unnecessary(),
+            );
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+pub fn redundant_stack_op(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    adjacent_pairs(doc, out, &[("dup", "splash"), ("swap", "swap")]);
+}
+
+pub fn gulp_burp_noop(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    adjacent_pairs(doc, out, &[("gulp", "burp"), ("burp", "gulp")]);
+}
+
+pub fn unreachable_code(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    let instrs = program_instructions(&doc.tree);
+    let mut i = 0;
+    while i < instrs.len() {
+        if instrs[i].kind() != "hop" {
+            i += 1;
+            continue;
+        }
+        let first = i + 1;
+        let mut last = first;
+        while last < instrs.len() && instrs[last].kind() != "label_definition" {
+            last += 1;
+        }
+        if last > first {
+            out.push(
+                Finding::new(span(instrs[first], instrs[last - 1]), "Unreachable code")
+                    .unnecessary(),
+            );
+        }
+        i = last;
+    }
+}
+
+pub fn jump_to_next_line(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    let instrs = program_instructions(&doc.tree);
+    for pair in instrs.windows(2) {
+        let (jump, next) = (pair[0], pair[1]);
+        if !matches!(jump.kind(), "hop" | "leap") || next.kind() != "label_definition" {
+            continue;
+        }
+        let (Some(target), Some(name)) = (label_name(jump, doc), label_name(next, doc)) else {
+            continue;
+        };
+        if target != name {
+            continue;
+        }
+        let message = if jump.kind() == "hop" {
+            format!("HOP to `{name}` jumps to the next line and has no effect")
+        } else {
+            format!("LEAP to `{name}` jumps to the next line; it only pops the stack")
+        };
+        out.push(Finding::new(node_byte_range(jump), message).unnecessary());
+    }
+}
+
+fn values(n: i64) -> String {
+    match n {
+        1 => "1 value".to_string(),
+        n => format!("{n} values"),
+    }
+}
+
+pub fn stack_effect_mismatch(doc: &Doc, _: &LintConfig, out: &mut Vec<Finding>) {
+    let index = doc.index();
+    let blocks = &doc.stack_analysis().blocks;
+
+    for name in label_names(index) {
+        let Some(declared) = index.stack_effects.get(name) else {
+            continue;
+        };
+        let Some(actual) = blocks.get(name) else {
+            continue;
+        };
+        let inputs = declared.inputs.len() as i64;
+        let outputs = declared.outputs.len() as i64;
+        let message = match actual.net {
+            None => format!(
+                "`{name}` is declared `{declared}`, but leaves a different number of values depending on where it exits"
+            ),
+            Some(net) if i64::from(actual.consumes) > inputs || net != outputs - inputs => {
+                // Counted against the declared inputs when the block takes no more than those
+                let takes = inputs.max(actual.consumes.into());
+                format!(
+                    "`{name}` is declared `{declared}`, but takes {} and leaves {}",
+                    values(takes),
+                    values(takes + net)
+                )
+            }
+            Some(_) => continue,
+        };
+        out.push(Finding::new(declared.range.clone(), message));
+    }
+}
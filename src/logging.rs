@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use tower_lsp::lsp_types::{MessageType, TraceValue};
+
+// Read at startup; the command line takes precedence
+pub const LOG_ENV: &str = "FROGGY_LOG";
+pub const LOG_FILE_ENV: &str = "FROGGY_LOG_FILE";
+
+// Where a record comes from, so each area can be turned up or down on its own
+pub mod target {
+    pub const SERVER: &str = "server";
+    pub const CONFIG: &str = "config";
+    pub const DOCUMENTS: &str = "documents";
+    pub const DIAGNOSTICS: &str = "diagnostics";
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warning,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "error" => Some(Self::Error),
+            "warning" | "warn" => Some(Self::Warning),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warning => "warning",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+
+    // How a record at this level is shown through window/logMessage
+    pub fn message_type(self) -> MessageType {
+        match self {
+            Self::Error => MessageType::ERROR,
+            Self::Warning => MessageType::WARNING,
+            Self::Info => MessageType::INFO,
+            _ => MessageType::LOG,
+        }
+    }
+}
+
+// A default level plus per-target overrides, written like `warning,documents=debug`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LogFilter {
+    default: LogLevel,
+    targets: HashMap<String, LogLevel>,
+}
+
+impl LogFilter {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (target, level) = match part.split_once('=') {
+                Some((target, level)) => (Some(target.trim()), level.trim()),
+                None => (None, part),
+            };
+            let level =
+                LogLevel::parse(level).ok_or_else(|| format!("unknown log level `{level}`"))?;
+            match target {
+                Some(target) => {
+                    filter.targets.insert(target.to_string(), level);
+                }
+                None => filter.default = level,
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn enabled(&self, target: &str, level: LogLevel) -> bool {
+        let max = self.targets.get(target).copied().unwrap_or(self.default);
+        level != LogLevel::Off && level <= max
+    }
+}
+
+impl TryFrom<String> for LogFilter {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, String> {
+        Self::parse(&s)
+    }
+}
+
+// Logging requested when the server was started
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    // Replaces the client's `logLevel` setting when given
+    pub filter: Option<LogFilter>,
+    pub file: Option<PathBuf>,
+}
+
+impl LogOptions {
+    pub fn from_env() -> Result<Self, String> {
+        let filter = match std::env::var(LOG_ENV) {
+            Ok(spec) => Some(LogFilter::parse(&spec).map_err(|e| format!("{LOG_ENV}: {e}"))?),
+            Err(_) => None,
+        };
+        let file = std::env::var_os(LOG_FILE_ENV).map(PathBuf::from);
+        Ok(Self { filter, file })
+    }
+}
+
+// Server-side logging state: the pinned filter, the log file and the client's trace level
+#[derive(Debug, Default)]
+pub struct Logger {
+    pinned: Option<LogFilter>,
+    file: Option<Arc<Mutex<File>>>,
+    trace: Mutex<TraceValue>,
+}
+
+impl Logger {
+    pub fn new(options: LogOptions) -> io::Result<Self> {
+        let file = match options.file {
+            Some(path) => Some(Arc::new(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ))),
+            None => None,
+        };
+        Ok(Self {
+            pinned: options.filter,
+            file,
+            trace: Mutex::new(TraceValue::Off),
+        })
+    }
+
+    // A logger for another client connection: same filter and file, tracing off
+    pub fn session(&self) -> Self {
+        Self {
+            pinned: self.pinned.clone(),
+            file: self.file.clone(),
+            trace: Mutex::new(TraceValue::Off),
+        }
+    }
+
+    // The filter in force, given the one from the client's settings
+    pub fn filter<'a>(&'a self, configured: &'a LogFilter) -> &'a LogFilter {
+        self.pinned.as_ref().unwrap_or(configured)
+    }
+
+    // Append a record to the log file, if there is one
+    pub fn write(&self, level: LogLevel, target: &str, message: &str) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut file = file.lock().unwrap();
+        // Losing a log line is better than failing the request that logged it
+        let _ = writeln!(
+            file,
+            "{}.{:03} {:<7} {target}: {message}",
+            now.as_secs(),
+            now.subsec_millis(),
+            level.name().to_ascii_uppercase()
+        );
+    }
+
+    pub fn trace(&self) -> TraceValue {
+        *self.trace.lock().unwrap()
+    }
+
+    pub fn set_trace(&self, value: TraceValue) {
+        *self.trace.lock().unwrap() = value;
+    }
+}
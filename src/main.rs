@@ -1,17 +1,77 @@
-mod backend;
-mod diagnostics;
-mod document;
-mod semantic_tokens;
-mod utils;
+use std::path::PathBuf;
+use std::process;
 
-use backend::Backend;
-use tower_lsp::{LspService, Server};
+use lsp_froggy::logging::{LOG_ENV, LOG_FILE_ENV, LogFilter, LogOptions, Logger};
+use lsp_froggy::transport::{self, Transport};
+
+fn usage() -> String {
+    format!(
+        "Usage: lsp-froggy [--stdio | --listen <port> | --connect <host:port> | --socket <path>]
+                  [--log-level <filter>] [--log-file <path>]
+
+  --stdio               talk to one client over stdin/stdout (default)
+  --listen <port>       accept clients on 127.0.0.1:<port>
+  --connect <host:port> connect to a client listening on <host:port>
+  --socket <path>       accept clients on a Unix socket at <path>
+  --log-level <filter>  e.g. `debug` or `warning,documents=trace` (env: {LOG_ENV})
+  --log-file <path>     also append log records to <path> (env: {LOG_FILE_ENV})"
+    )
+}
+
+// Logging options come from the environment, overridden by the command line
+fn parse_args() -> Result<(Transport, LogOptions), String> {
+    let mut transport = Transport::Stdio;
+    let mut options = LogOptions::from_env()?;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdio" => transport = Transport::Stdio,
+            "--listen" => {
+                let port = args.next().ok_or("--listen needs a port")?;
+                let port = port.parse().map_err(|_| format!("invalid port `{port}`"))?;
+                transport = Transport::Listen(port);
+            }
+            "--connect" => {
+                let addr = args.next().ok_or("--connect needs a host:port")?;
+                transport = Transport::Connect(addr);
+            }
+            "--socket" => {
+                let path = args.next().ok_or("--socket needs a path")?;
+                transport = Transport::Socket(PathBuf::from(path));
+            }
+            "--log-level" => {
+                let spec = args.next().ok_or("--log-level needs a value")?;
+                options.filter = Some(LogFilter::parse(&spec)?);
+            }
+            "--log-file" => {
+                let path = args.next().ok_or("--log-file needs a value")?;
+                options.file = Some(PathBuf::from(path));
+            }
+            "-h" | "--help" => {
+                println!("{}", usage());
+                process::exit(0);
+            }
+            other => return Err(format!("unknown argument `{other}`")),
+        }
+    }
+    Ok((transport, options))
+}
 
 #[tokio::main]
 async fn main() {
-    let stdin = tokio::io::stdin();
-    let stdout = tokio::io::stdout();
-    let (service, socket) = LspService::new(Backend::new);
+    let (transport, options) = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{}", usage());
+        process::exit(2);
+    });
+    let logger = Logger::new(options.clone()).unwrap_or_else(|e| {
+        let path = options.file.unwrap_or_default();
+        eprintln!("Failed to open log file {}: {e}", path.display());
+        process::exit(2);
+    });
 
-    Server::new(stdin, stdout, socket).serve(service).await;
+    if let Err(e) = transport::serve(transport, logger).await {
+        eprintln!("{e}");
+        process::exit(1);
+    }
 }
@@ -0,0 +1,125 @@
+use std::ops::Range;
+use std::sync::atomic::AtomicBool;
+
+use tower_lsp::lsp_types::Position;
+
+use crate::document::{Doc, ParseError};
+
+// Info strings that mark a fenced block as Froggy source
+const FROGGY_INFO: [&str; 2] = ["froggy", "frog"];
+
+// The fenced Froggy blocks of a Markdown file, each parsed as its own program.
+// A block's Doc holds the whole file with everything outside the block blanked,
+// so its offsets and positions are those of the Markdown file itself.
+#[derive(Debug)]
+pub struct MarkdownDoc {
+    pub version: i32,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub struct Block {
+    // Lines of the block's contents, excluding the fences
+    pub lines: Range<u32>,
+    pub doc: Doc,
+}
+
+impl MarkdownDoc {
+    pub fn parse(text: &str, version: i32, cancel: &AtomicBool) -> Result<Self, ParseError> {
+        let blocks = froggy_blocks(text)
+            .into_iter()
+            .map(|(lines, bytes)| {
+                let doc = Doc::parse(blank_outside(text, bytes), version, cancel)?;
+                Ok(Block { lines, doc })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { version, blocks })
+    }
+
+    // The block containing `position`, if any
+    pub fn block_at(&self, position: Position) -> Option<&Doc> {
+        self.blocks
+            .iter()
+            .find(|b| b.lines.contains(&position.line))
+            .map(|b| &b.doc)
+    }
+}
+
+// Opening fence: up to three spaces, then at least three backticks or tildes
+fn opening_fence(line: &str) -> Option<(char, usize, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.len() - trimmed.trim_start_matches(ch).len();
+    if len < 3 {
+        return None;
+    }
+    let info = trimmed[len..].trim();
+    // A backtick fence's info string may not contain backticks
+    if ch == '`' && info.contains('`') {
+        return None;
+    }
+    Some((ch, len, info))
+}
+
+fn closes(line: &str, ch: char, len: usize) -> bool {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 {
+        return false;
+    }
+    let rest = trimmed.trim_start_matches(ch);
+    trimmed.len() - rest.len() >= len && rest.trim().is_empty()
+}
+
+// Line and byte ranges of the contents of every fenced Froggy block
+fn froggy_blocks(text: &str) -> Vec<(Range<u32>, Range<usize>)> {
+    let mut blocks = Vec::new();
+    // Open fence: (char, length, is froggy, first content line, first content byte)
+    let mut open: Option<(char, usize, bool, u32, usize)> = None;
+
+    let mut offset = 0;
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let i = i as u32;
+        let content = line.trim_end_matches(['\n', '\r']);
+        match open {
+            None => {
+                if let Some((ch, len, info)) = opening_fence(content) {
+                    let lang = info.split_whitespace().next().unwrap_or("");
+                    let froggy = FROGGY_INFO.iter().any(|l| l.eq_ignore_ascii_case(lang));
+                    open = Some((ch, len, froggy, i + 1, offset + line.len()));
+                }
+            }
+            Some((ch, len, froggy, first_line, start)) => {
+                if closes(content, ch, len) {
+                    if froggy {
+                        blocks.push((first_line..i, start..offset));
+                    }
+                    open = None;
+                }
+            }
+        }
+        offset += line.len();
+    }
+
+    // An unclosed fence runs to the end of the file
+    if let Some((_, _, true, first_line, start)) = open {
+        let lines = text.split_inclusive('\n').count() as u32;
+        blocks.push((first_line..lines.max(first_line), start..text.len()));
+    }
+    blocks
+}
+
+// Replace everything outside `keep` with spaces, keeping line breaks and byte offsets
+fn blank_outside(text: &str, keep: Range<usize>) -> String {
+    let blank = |s: &str| -> String {
+        s.bytes()
+            .map(|b| if b == b'\n' { '\n' } else { ' ' })
+            .collect()
+    };
+    let mut out = blank(&text[..keep.start]);
+    out.push_str(&text[keep.clone()]);
+    out.push_str(&blank(&text[keep.end..]));
+    out
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::ops::Range as Lines;
+use std::sync::atomic::AtomicBool;
+
+use serde::Deserialize;
+use tower_lsp::lsp_types::{
+    Position, Range, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem, Url,
+    VersionedTextDocumentIdentifier,
+};
+
+use crate::document::{Doc, ParseError};
+
+// LSP 3.17 notebook sync messages, which lsp-types 0.94 doesn't define yet.
+// Only the fields the server uses are declared.
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidOpenNotebookDocumentParams {
+    pub notebook_document: NotebookDocument,
+    pub cell_text_documents: Vec<TextDocumentItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookDocument {
+    pub uri: Url,
+    pub version: i32,
+    pub cells: Vec<NotebookCell>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotebookCell {
+    pub kind: NotebookCellKind,
+    pub document: Url,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum NotebookCellKind {
+    Markup,
+    Code,
+}
+
+impl TryFrom<u8> for NotebookCellKind {
+    type Error = String;
+
+    fn try_from(kind: u8) -> Result<Self, String> {
+        match kind {
+            1 => Ok(Self::Markup),
+            2 => Ok(Self::Code),
+            other => Err(format!("unknown notebook cell kind {other}")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidChangeNotebookDocumentParams {
+    pub notebook_document: VersionedNotebookDocumentIdentifier,
+    pub change: NotebookDocumentChangeEvent,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VersionedNotebookDocumentIdentifier {
+    pub version: i32,
+    pub uri: Url,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotebookDocumentChangeEvent {
+    #[serde(default)]
+    pub cells: Option<NotebookCellsChange>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellsChange {
+    pub structure: Option<NotebookCellsStructureChange>,
+    pub data: Option<Vec<NotebookCell>>,
+    pub text_content: Option<Vec<NotebookCellTextChange>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellsStructureChange {
+    pub array: NotebookCellArrayChange,
+    pub did_open: Option<Vec<TextDocumentItem>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotebookCellArrayChange {
+    pub start: u32,
+    pub delete_count: u32,
+    pub cells: Option<Vec<NotebookCell>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NotebookCellTextChange {
+    pub document: VersionedTextDocumentIdentifier,
+    pub changes: Vec<TextDocumentContentChangeEvent>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DidCloseNotebookDocumentParams {
+    pub notebook_document: TextDocumentIdentifier,
+}
+
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub uri: Url,
+    pub kind: NotebookCellKind,
+    pub language: String,
+    pub version: i32,
+    pub text: String,
+}
+
+impl Cell {
+    // Markup cells and cells in other languages aren't part of the program
+    pub fn is_froggy(&self) -> bool {
+        self.kind == NotebookCellKind::Code && matches!(self.language.as_str(), "froggy" | "frog")
+    }
+}
+
+// Build the cell list of a newly opened notebook
+pub fn open_cells(params: DidOpenNotebookDocumentParams) -> Vec<Cell> {
+    let mut items: HashMap<Url, TextDocumentItem> = params
+        .cell_text_documents
+        .into_iter()
+        .map(|item| (item.uri.clone(), item))
+        .collect();
+
+    params
+        .notebook_document
+        .cells
+        .into_iter()
+        .map(|cell| new_cell(cell, &mut items))
+        .collect()
+}
+
+fn new_cell(cell: NotebookCell, items: &mut HashMap<Url, TextDocumentItem>) -> Cell {
+    let item = items.remove(&cell.document);
+    Cell {
+        kind: cell.kind,
+        language: item
+            .as_ref()
+            .map_or_else(String::new, |i| i.language_id.clone()),
+        version: item.as_ref().map_or(0, |i| i.version),
+        text: item.map_or_else(String::new, |i| i.text),
+        uri: cell.document,
+    }
+}
+
+// Apply a notebook change to its cells, returning the URIs of cells that were removed
+pub fn apply_change(cells: &mut Vec<Cell>, change: NotebookDocumentChangeEvent) -> Vec<Url> {
+    let Some(change) = change.cells else {
+        return Vec::new();
+    };
+    let mut removed = Vec::new();
+
+    if let Some(structure) = change.structure {
+        let mut items: HashMap<Url, TextDocumentItem> = structure
+            .did_open
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| (item.uri.clone(), item))
+            .collect();
+        let new_cells: Vec<Cell> = structure
+            .array
+            .cells
+            .unwrap_or_default()
+            .into_iter()
+            .map(|cell| new_cell(cell, &mut items))
+            .collect();
+
+        let start = (structure.array.start as usize).min(cells.len());
+        let end = (start + structure.array.delete_count as usize).min(cells.len());
+        removed.extend(cells.splice(start..end, new_cells).map(|c| c.uri));
+    }
+
+    for cell in change.data.unwrap_or_default() {
+        if let Some(c) = cells.iter_mut().find(|c| c.uri == cell.document) {
+            c.kind = cell.kind;
+        }
+    }
+
+    // Cells are synced in full, like text documents
+    for edit in change.text_content.unwrap_or_default() {
+        let Some(c) = cells.iter_mut().find(|c| c.uri == edit.document.uri) else {
+            continue;
+        };
+        if let Some(last) = edit.changes.into_iter().last() {
+            c.text = last.text;
+            c.version = edit.document.version;
+        }
+    }
+
+    // Cells moved within the notebook are removed and re-added in the same change
+    removed.retain(|uri| !cells.iter().any(|c| &c.uri == uri));
+    removed
+}
+
+// Lines of the concatenated program that came from one cell
+#[derive(Clone, Debug)]
+pub struct CellSpan {
+    pub uri: Url,
+    pub version: i32,
+    pub lines: Lines<u32>,
+}
+
+// A notebook's Froggy cells joined into one program, so labels resolve across cells
+#[derive(Debug)]
+pub struct Notebook {
+    pub version: i32,
+    pub cells: Vec<Cell>,
+    pub doc: Doc,
+    pub spans: Vec<CellSpan>,
+}
+
+impl Notebook {
+    pub fn parse(cells: Vec<Cell>, version: i32, cancel: &AtomicBool) -> Result<Self, ParseError> {
+        let mut text = String::new();
+        let mut spans = Vec::new();
+        let mut line = 0;
+
+        for cell in cells.iter().filter(|c| c.is_froggy()) {
+            let start = line;
+            text.push_str(&cell.text);
+            // Every cell starts on a line of its own
+            if !cell.text.ends_with('\n') {
+                text.push('\n');
+            }
+            line += cell.text.lines().count().max(1) as u32;
+            spans.push(CellSpan {
+                uri: cell.uri.clone(),
+                version: cell.version,
+                lines: start..line,
+            });
+        }
+
+        let doc = Doc::parse(text, version, cancel)?;
+        Ok(Self {
+            version,
+            cells,
+            doc,
+            spans,
+        })
+    }
+
+    pub fn span(&self, cell: &Url) -> Option<&CellSpan> {
+        self.spans.iter().find(|s| &s.uri == cell)
+    }
+
+    // Position in the concatenated program of `position` in `cell`
+    pub fn to_doc(&self, cell: &Url, position: Position) -> Option<Position> {
+        let span = self.span(cell)?;
+        Some(Position::new(
+            span.lines.start + position.line,
+            position.character,
+        ))
+    }
+
+    // The cell a range of the concatenated program lies in, and the range within it
+    pub fn to_cell(&self, range: Range) -> Option<(&CellSpan, Range)> {
+        let span = self
+            .spans
+            .iter()
+            .find(|s| s.lines.contains(&range.start.line))?;
+        let shift = |p: Position| Position::new(p.line - span.lines.start, p.character);
+        // Ranges running past the cell are cut at its last line
+        let end = if span.lines.contains(&range.end.line) {
+            shift(range.end)
+        } else {
+            Position::new(span.lines.end - span.lines.start, 0)
+        };
+        Some((span, Range::new(shift(range.start), end)))
+    }
+}
+
+// The notebook owning a cell
+pub fn owner<'a>(notebooks: &'a HashMap<Url, Notebook>, cell: &Url) -> Option<&'a Notebook> {
+    notebooks.values().find(|nb| nb.span(cell).is_some())
+}
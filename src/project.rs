@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use line_index::{LineIndex, TextSize, WideEncoding};
+use toml_edit::{Document, Item, TableLike};
+use tower_lsp::lsp_types::*;
+
+use crate::lint::{LintConfig, RuleLevel, find_rule};
+
+pub const CONFIG_FILE: &str = "froggy.toml";
+
+// Diagnostic code for problems in the config file itself
+pub const CONFIG_CODE: &str = "invalid-config";
+
+// Settings checked into the repository, layered over the client's settings
+#[derive(Clone, Debug, Default)]
+pub struct ProjectConfig {
+    pub lints: LintConfig,
+}
+
+// Nearest `froggy.toml` in `dir` or any of its ancestors
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE))
+        .find(|p| p.is_file())
+}
+
+struct TomlDiags<'a> {
+    line_index: LineIndex,
+    text: &'a str,
+    out: Vec<Diagnostic>,
+}
+
+impl TomlDiags<'_> {
+    fn push(&mut self, span: Option<std::ops::Range<usize>>, message: String) {
+        let span = span.unwrap_or(0..0);
+        // LSP columns count UTF-16 code units
+        let pos = |offset: usize| {
+            let lc = self
+                .line_index
+                .line_col(TextSize::from(offset.min(self.text.len()) as u32));
+            match self.line_index.to_wide(WideEncoding::Utf16, lc) {
+                Some(wide) => Position::new(wide.line, wide.col),
+                None => Position::new(lc.line, lc.col),
+            }
+        };
+        self.out.push(Diagnostic {
+            range: Range::new(pos(span.start), pos(span.end)),
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: Some(NumberOrString::String(CONFIG_CODE.to_string())),
+            source: Some("froggy".to_string()),
+            message,
+            ..Default::default()
+        });
+    }
+}
+
+fn key_span(table: &dyn TableLike, key: &str) -> Option<std::ops::Range<usize>> {
+    table.get_key_value(key).and_then(|(k, _)| k.span())
+}
+
+// Parse `froggy.toml`, reporting syntax errors, unknown keys and bad values
+pub fn parse_config(text: &str) -> (ProjectConfig, Vec<Diagnostic>) {
+    let mut config = ProjectConfig::default();
+    let mut diags = TomlDiags {
+        line_index: LineIndex::new(text),
+        text,
+        out: Vec::new(),
+    };
+
+    let doc = match Document::parse(text) {
+        Ok(doc) => doc,
+        Err(e) => {
+            diags.push(e.span(), e.message().to_string());
+            return (config, diags.out);
+        }
+    };
+    let root = doc.as_table();
+
+    for (key, item) in root.iter() {
+        match key {
+            "lints" => read_lints(root, item, &mut config, &mut diags),
+            "entry" => read_entry(item, &mut config, &mut diags),
+            _ => diags.push(key_span(root, key), format!("Unknown key `{key}`")),
+        }
+    }
+
+    (config, diags.out)
+}
+
+fn read_lints(
+    root: &dyn TableLike,
+    item: &Item,
+    config: &mut ProjectConfig,
+    diags: &mut TomlDiags,
+) {
+    let Some(table) = item.as_table_like() else {
+        diags.push(
+            key_span(root, "lints"),
+            "`lints` must be a table".to_string(),
+        );
+        return;
+    };
+
+    for (rule, level) in table.iter() {
+        if find_rule(rule).is_none() {
+            diags.push(key_span(table, rule), format!("Unknown lint rule `{rule}`"));
+            continue;
+        }
+        match level.as_str().and_then(RuleLevel::parse) {
+            Some(level) => {
+                config.lints.overrides.insert(rule.to_string(), level);
+            }
+            None => diags.push(
+                level.span(),
+                "Expected one of \"off\", \"hint\", \"info\", \"warning\", \"error\"".to_string(),
+            ),
+        }
+    }
+}
+
+fn read_entry(item: &Item, config: &mut ProjectConfig, diags: &mut TomlDiags) {
+    let Some(array) = item.as_array() else {
+        diags.push(
+            item.span(),
+            "`entry` must be an array of label names".to_string(),
+        );
+        return;
+    };
+
+    for value in array.iter() {
+        match value.as_str() {
+            Some(label) => config.lints.entry_points.push(label.to_string()),
+            None => diags.push(value.span(), "Expected a label name".to_string()),
+        }
+    }
+}
+
+// Loaded project configs, and which one applies to each open document
+#[derive(Debug, Default)]
+pub struct ProjectStore {
+    // Whether the client can notify us when config files change on disk
+    pub can_watch: bool,
+    configs: HashMap<PathBuf, ProjectConfig>,
+    by_doc: HashMap<Url, Option<PathBuf>>,
+}
+
+// What looking for the config of a document found on disk
+#[derive(Debug, Default)]
+pub struct Discovery {
+    path: Option<PathBuf>,
+    // The file parsed, if it could be read
+    loaded: Option<(ProjectConfig, Vec<Diagnostic>)>,
+}
+
+impl Discovery {
+    // Find and read the config governing `uri`. Blocking, so not to be called while
+    // holding the store's lock.
+    pub fn run(uri: &Url) -> Self {
+        let path = uri
+            .to_file_path()
+            .ok()
+            .and_then(|p| find_config(p.parent()?));
+        let loaded = path
+            .as_deref()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|text| parse_config(&text));
+        Self { path, loaded }
+    }
+}
+
+impl ProjectStore {
+    // Project config governing `uri`: Some(None) if it has none, and None if that isn't
+    // known yet and a Discovery must be run
+    pub fn cached(&self, uri: &Url) -> Option<Option<&ProjectConfig>> {
+        match self.by_doc.get(uri)? {
+            Some(path) => self.configs.get(path).map(Some),
+            None => Some(None),
+        }
+    }
+
+    // Record what a Discovery found for `uri`. Returns the config, and diagnostics for
+    // a config file that wasn't loaded before.
+    pub fn insert(
+        &mut self,
+        uri: &Url,
+        discovery: Discovery,
+    ) -> (Option<&ProjectConfig>, Option<(Url, Vec<Diagnostic>)>) {
+        let Discovery { path, loaded } = discovery;
+        self.by_doc.insert(uri.clone(), path.clone());
+        let Some(path) = path else {
+            return (None, None);
+        };
+
+        let mut diagnostics = None;
+        if !self.configs.contains_key(&path)
+            && let Some((config, diags)) = loaded
+        {
+            self.configs.insert(path.clone(), config);
+            diagnostics = Url::from_file_path(&path).ok().map(|u| (u, diags));
+        }
+        (self.configs.get(&path), diagnostics)
+    }
+
+    // Forget everything after config files are created, changed or deleted
+    pub fn invalidate(&mut self) {
+        self.configs.clear();
+        self.by_doc.clear();
+    }
+}
@@ -0,0 +1,54 @@
+use tower_lsp::lsp_types::{Position, SelectionRange};
+
+use crate::document::{ByteRange, Doc};
+use crate::utils::froggy_helpers::label_block_range;
+use crate::utils::tree_sitter_helpers::{find_node_at_position, labeldef_to_range};
+
+// Build nested selection ranges for a position: token, instruction, label block, whole file
+pub fn selection_range_at(doc: &Doc, position: Position) -> SelectionRange {
+    let offset = doc.lsp_position_to_offset(position).unwrap_or(0);
+    let node = find_node_at_position(&doc.tree, doc, position);
+
+    // Innermost to outermost
+    let mut ranges: Vec<ByteRange> = Vec::new();
+    let mut cur = Some(node);
+    while let Some(n) = cur {
+        // The root is replaced by the whole-file range below
+        if n.parent().is_none() {
+            break;
+        }
+        if n.start_byte() <= offset && offset <= n.end_byte() {
+            ranges.push(ByteRange {
+                start: n.start_byte(),
+                end: n.end_byte(),
+            });
+        }
+        cur = n.parent();
+    }
+
+    // Slot the label block in before the first range it doesn't contain
+    if let Some(block) = label_block_range(doc.index(), &doc.text, offset) {
+        let at = ranges
+            .iter()
+            .position(|r| r.start < block.start || r.end > block.end)
+            .unwrap_or(ranges.len());
+        ranges.insert(at, block);
+    }
+
+    ranges.push(ByteRange {
+        start: 0,
+        end: doc.text.len(),
+    });
+    ranges.dedup_by(|a, b| a.start == b.start && a.end == b.end);
+
+    // Fold from the outside in so each range points at its parent
+    let mut parent: Option<Box<SelectionRange>> = None;
+    for r in ranges.iter().rev() {
+        parent = Some(Box::new(SelectionRange {
+            range: labeldef_to_range(r, doc),
+            parent,
+        }));
+    }
+
+    *parent.expect("whole-file range is always present")
+}
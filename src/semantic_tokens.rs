@@ -13,6 +13,42 @@ pub struct Tok {
     mods: u32, // bitset index into legend.token_modifiers
 }
 
+impl Tok {
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn token_type(&self) -> u32 {
+        self.ty
+    }
+
+    pub fn modifiers(&self) -> u32 {
+        self.mods
+    }
+
+    // Where the token lies in `doc`'s text
+    pub fn byte_range(&self, doc: &Doc) -> Option<ByteRange> {
+        let at = |character| {
+            doc.lsp_position_to_offset(Position {
+                line: self.line,
+                character,
+            })
+        };
+        Some(ByteRange {
+            start: at(self.col)?,
+            end: at(self.col + self.len)?,
+        })
+    }
+
+    // The same token `lines` further up, for documents embedded in a larger one
+    pub fn shifted_up(self, lines: u32) -> Self {
+        Self {
+            line: self.line - lines,
+            ..self
+        }
+    }
+}
+
 pub fn legend() -> SemanticTokensLegend {
     SemanticTokensLegend {
         token_types: vec![
@@ -153,10 +189,10 @@ pub fn build_semantic_tokens(doc: &Doc) -> Vec<Tok> {
 
             "identifier" => {
                 // Skip identifiers hop/leap/label_definition idents
-                if let Some(parent) = node.parent() {
-                    if matches!(parent.kind(), "label_definition" | "hop" | "leap") {
-                        return;
-                    }
+                if let Some(parent) = node.parent()
+                    && matches!(parent.kind(), "label_definition" | "hop" | "leap")
+                {
+                    return;
                 }
                 add_token_range(&mut toks, doc, node_range(node), token_types::VARIABLE, 0);
             }
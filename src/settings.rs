@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+use tower_lsp::lsp_types::Url;
+
+use crate::lint::{LintConfig, RuleLevel};
+use crate::logging::LogFilter;
+
+// Client-provided settings, read from `initializationOptions` and the `froggy` configuration section
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct Settings {
+    pub lints: LintConfig,
+    pub log_level: LogFilter,
+    // Milliseconds to wait after an edit before recomputing diagnostics
+    pub diagnostic_delay: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            lints: LintConfig::default(),
+            log_level: LogFilter::default(),
+            diagnostic_delay: 200,
+        }
+    }
+}
+
+impl Settings {
+    // Parse a settings object, returning the settings plus any problems that didn't stop
+    // parsing. An invalid `logLevel` leaves the level at `log_level`.
+    pub fn from_json(
+        mut value: Value,
+        log_level: &LogFilter,
+    ) -> Result<(Self, Vec<String>), String> {
+        if value.is_null() {
+            return Ok((Self::default(), Vec::new()));
+        }
+
+        // Lint levels and the log level are read on their own, so a bad one doesn't cost
+        // the other settings
+        let (lints, level) = match value.as_object_mut() {
+            Some(o) => (o.remove("lints"), o.remove("logLevel")),
+            None => (None, None),
+        };
+        let mut settings: Self = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let mut problems = Vec::new();
+        if let Some(lints) = lints {
+            settings.lints = read_lints(lints, &mut problems);
+        }
+        if let Some(level) = level {
+            settings.log_level = serde_json::from_value(level).unwrap_or_else(|e| {
+                problems.push(format!("{e} for `logLevel`; keeping the current level"));
+                log_level.clone()
+            });
+        }
+        problems.extend(
+            settings
+                .lints
+                .unknown_rules()
+                .map(|id| format!("unknown lint rule `{id}`")),
+        );
+
+        Ok((settings, problems))
+    }
+}
+
+fn read_lints(lints: Value, problems: &mut Vec<String>) -> LintConfig {
+    let Value::Object(lints) = lints else {
+        problems.push("`lints` must be an object".to_string());
+        return LintConfig::default();
+    };
+    let mut overrides = HashMap::new();
+    for (rule, level) in lints {
+        match serde_json::from_value::<RuleLevel>(level) {
+            Ok(level) => {
+                overrides.insert(rule, level);
+            }
+            Err(e) => problems.push(format!("{e} for lint rule `{rule}`")),
+        }
+    }
+    LintConfig::from(overrides)
+}
+
+#[derive(Debug, Default)]
+pub struct SettingsStore {
+    pub global: Settings,
+    // Workspace folders with their scoped settings (global until the client is asked)
+    pub folders: Vec<(Url, Settings)>,
+    // Whether the client answers `workspace/configuration` requests
+    pub can_pull: bool,
+}
+
+impl SettingsStore {
+    // Settings of the innermost workspace folder containing `uri`
+    pub fn for_uri(&self, uri: &Url) -> &Settings {
+        self.folders
+            .iter()
+            .filter(|(folder, _)| contains(folder, uri))
+            .max_by_key(|(folder, _)| folder.as_str().len())
+            .map_or(&self.global, |(_, settings)| settings)
+    }
+
+    pub fn in_workspace(&self, uri: &Url) -> bool {
+        self.folders.iter().any(|(folder, _)| contains(folder, uri))
+    }
+}
+
+fn contains(folder: &Url, uri: &Url) -> bool {
+    let prefix = format!("{}/", folder.as_str().trim_end_matches('/'));
+    uri.as_str().starts_with(&prefix)
+}
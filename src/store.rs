@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use tower_lsp::lsp_types::Url;
+
+use crate::document::Doc;
+
+// Open documents as immutable snapshots. Readers clone an `Arc` and release the lock
+// straight away, so a request always sees a whole document (never one half-way through
+// an edit) and keeps using it while newer versions are swapped in.
+#[derive(Debug, Default)]
+pub struct DocStore {
+    docs: RwLock<HashMap<Url, Arc<Doc>>>,
+}
+
+impl DocStore {
+    pub fn get(&self, uri: &Url) -> Option<Arc<Doc>> {
+        self.docs.read().unwrap().get(uri).cloned()
+    }
+
+    pub fn version(&self, uri: &Url) -> Option<i32> {
+        self.docs.read().unwrap().get(uri).map(|d| d.version)
+    }
+
+    pub fn uris(&self) -> Vec<Url> {
+        self.docs.read().unwrap().keys().cloned().collect()
+    }
+
+    // Store a newly opened document, replacing whatever was there
+    pub fn insert(&self, uri: Url, doc: Doc) -> Arc<Doc> {
+        let doc = Arc::new(doc);
+        self.docs.write().unwrap().insert(uri, doc.clone());
+        doc
+    }
+
+    // Swap in `doc` unless the store already holds a newer version, which happens
+    // when a slow parse of an older edit finishes last. Returns whether it was stored.
+    pub fn update(&self, uri: Url, mut doc: Doc) -> bool {
+        let mut docs = self.docs.write().unwrap();
+        match docs.get(&uri) {
+            Some(old) if old.version > doc.version => return false,
+            // Nothing the analyses depend on changed
+            Some(old) if old.text == doc.text => doc.queries = old.queries.clone(),
+            _ => {}
+        }
+        docs.insert(uri, Arc::new(doc));
+        true
+    }
+
+    pub fn remove(&self, uri: &Url) -> Option<Arc<Doc>> {
+        self.docs.write().unwrap().remove(uri)
+    }
+}
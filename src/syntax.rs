@@ -0,0 +1,267 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use tree_sitter::{LanguageRef, Node, Tree};
+
+use crate::document::ByteRange;
+use crate::utils::froggy_helpers::MNEMONICS;
+use crate::utils::tree_sitter_helpers::node_byte_range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub range: ByteRange,
+    pub message: String,
+    // Mnemonic to replace the range with, for a misspelled instruction
+    pub suggestion: Option<&'static str>,
+    // Where the operand of an incomplete instruction goes
+    pub insertion: Option<Insertion>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Insertion {
+    // Byte offset just after the instruction's mnemonic
+    pub at: usize,
+    pub operand: Operand,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Operand {
+    // Of PLOP
+    Value,
+    // Of HOP and LEAP
+    Target,
+    // Of LILY
+    LabelName,
+}
+
+impl Operand {
+    fn after(mnemonic: &str) -> Option<Self> {
+        match mnemonic {
+            "PLOP" => Some(Self::Value),
+            "HOP" | "LEAP" => Some(Self::Target),
+            "LILY" => Some(Self::LabelName),
+            _ => None,
+        }
+    }
+}
+
+// The operand a mnemonic token needs, if the instruction stops right after it
+fn insertion_after(token: Node) -> Option<Insertion> {
+    Some(Insertion {
+        at: token.end_byte(),
+        operand: Operand::after(token.kind())?,
+    })
+}
+
+// One error per outermost ERROR node and per MISSING node outside of one,
+// saying what the parser expected there
+pub fn syntax_errors(tree: &Tree, text: &str) -> Vec<SyntaxError> {
+    let mut out = Vec::new();
+    let mut stack = vec![tree.root_node()];
+    while let Some(node) = stack.pop() {
+        if node.is_error() {
+            // Errors nested in this one are part of the same mistake
+            out.push(error_node(node, text));
+            continue;
+        }
+        if node.is_missing() {
+            out.push(missing_node(node, text));
+            continue;
+        }
+        let mut cursor = node.walk();
+        stack.extend(node.children(&mut cursor));
+    }
+    out.sort_by_key(|e| e.range.start);
+    out
+}
+
+fn missing_node(node: Node, text: &str) -> SyntaxError {
+    let expected = describe_kind(node.kind()).unwrap_or(node.kind());
+    let message = match node.prev_sibling() {
+        Some(prev) => format!("Expected {expected} after {}", snippet(prev, text)),
+        None => format!("Expected {expected}"),
+    };
+    SyntaxError {
+        range: node_byte_range(node),
+        message,
+        suggestion: None,
+        insertion: node.prev_sibling().and_then(insertion_after),
+    }
+}
+
+// Find the first token of the ERROR node the parser couldn't accept. If it accepted
+// them all, the input ended (or the line moved on) before the construct was complete.
+fn error_node(node: Node, text: &str) -> SyntaxError {
+    let language = node.language();
+    let mut prev: Option<Node> = None;
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        let is_token = child.child_count() == 0 || child.is_error();
+        if is_token && !accepts(&language, child.parse_state(), child) {
+            let expected = expected_in(&language, child.parse_state());
+            if let Some(error) = unknown_instruction(child, text, expected.as_deref()) {
+                return error;
+            }
+            let mut message = format!("Unexpected {}", describe_token(child, text));
+            if let Some(expected) = expected {
+                message.push_str(&format!("; expected {expected}"));
+                if let Some(prev) = prev {
+                    message.push_str(&format!(" after {}", snippet(prev, text)));
+                }
+            }
+            return SyntaxError {
+                range: node_byte_range(child),
+                message,
+                suggestion: None,
+                insertion: None,
+            };
+        }
+        prev = Some(child);
+    }
+
+    let message = match prev.and_then(|p| Some((p, expected_in(&language, p.next_parse_state())?)))
+    {
+        Some((prev, expected)) => format!("Expected {expected} after {}", snippet(prev, text)),
+        None => format!("Syntax error near {}", snippet(node, text)),
+    };
+    SyntaxError {
+        range: node_byte_range(node),
+        message,
+        suggestion: None,
+        insertion: prev.and_then(insertion_after),
+    }
+}
+
+fn accepts(language: &LanguageRef, state: u16, token: Node) -> bool {
+    if token.is_error() {
+        return false;
+    }
+    language
+        .lookahead_iterator(state)
+        .is_some_and(|mut symbols| symbols.any(|s| s == token.kind_id()))
+}
+
+// Tokens valid in parse `state`, in words; None if there's nothing useful to say
+fn expected_in(language: &LanguageRef, state: u16) -> Option<String> {
+    let mut symbols = language.lookahead_iterator(state)?;
+    let mut seen = HashSet::new();
+    let names: Vec<&str> = symbols
+        .iter_names()
+        .filter_map(describe_kind)
+        .filter(|name| seen.insert(*name))
+        .collect();
+
+    match names.as_slice() {
+        [] => None,
+        [one] => Some(one.to_string()),
+        [rest @ .., last] => Some(format!("{} or {last}", rest.join(", "))),
+    }
+}
+
+// Terminals worth naming in a message; other symbols of the parse table are skipped
+fn describe_kind(kind: &str) -> Option<&'static str> {
+    match kind {
+        "identifier" => Some("label name"),
+        "number" => Some("number"),
+        "string" => Some("string"),
+        _ if MNEMONICS.iter().any(|m| m.eq_ignore_ascii_case(kind)) => Some("instruction"),
+        _ => None,
+    }
+}
+
+fn describe_token(token: Node, text: &str) -> String {
+    match describe_kind(token.kind()) {
+        Some(kind) if !token.is_error() && kind != "instruction" => {
+            format!("{kind} {}", snippet(token, text))
+        }
+        _ => snippet(token, text),
+    }
+}
+
+// Source of a node for a message: its first line, shortened if long
+fn snippet(node: Node, text: &str) -> String {
+    let source = node.utf8_text(text.as_bytes()).unwrap_or("");
+    let line = source.lines().next().unwrap_or("").trim_end();
+    match line.char_indices().nth(24) {
+        Some((cut, _)) => format!("`{}…`", &line[..cut]),
+        None => format!("`{line}`"),
+    }
+}
+
+// A word where an instruction should be, or a word the lexer split after a mnemonic
+// (`ADDD` lexes as `ADD` and `D`): most likely a misspelled mnemonic
+fn unknown_instruction(token: Node, text: &str, expected: Option<&str>) -> Option<SyntaxError> {
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let bytes = text.as_bytes();
+    let mut start = token.start_byte();
+    let mut end = token.start_byte();
+    while start > 0 && is_word(bytes[start - 1]) {
+        start -= 1;
+    }
+    while end < bytes.len() && is_word(bytes[end]) {
+        end += 1;
+    }
+
+    let word = &text[start..end];
+    if word.is_empty() || !word.bytes().all(|b| b.is_ascii_alphabetic() || b == b'_') {
+        return None;
+    }
+    let glued = start < token.start_byte();
+    if !glued && !expected.is_some_and(|e| e.contains("instruction")) {
+        return None;
+    }
+
+    let suggestion = closest_mnemonic(word);
+    let message = match suggestion {
+        // Spelled right, but only uppercase mnemonics lex
+        Some(m) if word.eq_ignore_ascii_case(m) => {
+            format!("Mnemonics are uppercase: `{word}` must be written as `{m}`")
+        }
+        Some(m) => format!("Unknown instruction `{word}`, did you mean `{m}`?"),
+        None => format!("Unknown instruction `{word}`"),
+    };
+    Some(SyntaxError {
+        range: ByteRange { start, end },
+        message,
+        suggestion,
+        insertion: None,
+    })
+}
+
+// Nearest mnemonic by edit distance, if near enough to be a typo
+pub fn closest_mnemonic(word: &str) -> Option<&'static str> {
+    let word = word.to_ascii_uppercase();
+    let max = (word.len() / 3).max(1);
+    MNEMONICS
+        .iter()
+        .map(|m| (edit_distance(&word, m), *m))
+        .filter(|&(d, _)| d <= max)
+        .min_by_key(|&(d, _)| d)
+        .map(|(_, m)| m)
+}
+
+// Levenshtein distance, counting a swap of adjacent characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
@@ -0,0 +1,137 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tower_lsp::Server;
+
+use crate::backend::Backend;
+use crate::logging::Logger;
+
+// How the server talks to its client(s)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Transport {
+    #[default]
+    Stdio,
+    // Accept any number of clients on 127.0.0.1:<port>
+    Listen(u16),
+    // Dial out to a client waiting on <host:port>
+    Connect(String),
+    // Accept any number of clients on a Unix domain socket
+    Socket(PathBuf),
+}
+
+pub async fn serve(transport: Transport, logger: Logger) -> io::Result<()> {
+    match transport {
+        Transport::Stdio => {
+            serve_connection(tokio::io::stdin(), tokio::io::stdout(), logger).await;
+            Ok(())
+        }
+        Transport::Listen(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+            serve_tcp(listener, logger).await
+        }
+        Transport::Connect(addr) => {
+            let (read, write) = TcpStream::connect(addr).await?.into_split();
+            serve_connection(read, write, logger).await;
+            Ok(())
+        }
+        Transport::Socket(path) => serve_unix(&path, logger).await,
+    }
+}
+
+// Serve one client until it exits or disconnects
+pub async fn serve_connection<I, O>(input: I, output: O, logger: Logger)
+where
+    I: AsyncRead + Unpin,
+    O: AsyncWrite,
+{
+    let (service, socket) = Backend::service(logger);
+    Server::new(input, output, socket).serve(service).await;
+}
+
+// Give every accepted connection its own server, until interrupted
+pub async fn serve_tcp(listener: TcpListener, logger: Logger) -> io::Result<()> {
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut shutdown => return Ok(()),
+        };
+        let (read, write) = stream.into_split();
+        tokio::spawn(serve_connection(read, write, logger.session()));
+    }
+}
+
+// Ctrl-C, or SIGTERM where there is one
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(unix)]
+async fn serve_unix(path: &Path, logger: Logger) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::net::{UnixListener, UnixStream};
+
+    // A socket file left behind by a server that didn't shut down cleanly, which
+    // refuses connections; a live server's socket and anything else at the path are
+    // left alone
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => match UnixStream::connect(path).await {
+            Ok(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by a running server", path.display()),
+                ));
+            }
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => std::fs::remove_file(path)?,
+            Err(e) => return Err(e),
+        },
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    let listener = UnixListener::bind(path)?;
+
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let result = loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => break Err(e),
+            },
+            _ = &mut shutdown => break Ok(()),
+        };
+        let (read, write) = stream.into_split();
+        tokio::spawn(serve_connection(read, write, logger.session()));
+    };
+
+    let _ = std::fs::remove_file(path);
+    result
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(_: &Path, _: Logger) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "Unix sockets are not supported on this platform",
+    ))
+}
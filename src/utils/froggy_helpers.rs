@@ -1,12 +1,45 @@
 use crate::document::{ByteRange, Doc, Index};
 use crate::utils::tree_sitter_helpers::labeldef_to_range;
-use tower_lsp::lsp_types::{Hover, HoverContents, MarkedString};
-use tree_sitter::Node;
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkedString, MarkupContent, MarkupKind};
+use tree_sitter::{Node, Tree};
+
+// Every instruction mnemonic the grammar accepts
+pub const MNEMONICS: &[&str] = &[
+    "RIBBIT",
+    "CROAK",
+    "PLOP",
+    "SPLASH",
+    "GULP",
+    "BURP",
+    "HOP",
+    "LEAP",
+    "LILY",
+    "DUP",
+    "SWAP",
+    "OVER",
+    "ADD",
+    "SUB",
+    "MUL",
+    "DIV",
+    "EQUALS",
+    "NOT_EQUAL",
+    "LESS_THAN",
+    "GREATER_THAN",
+    "LESS_EQ",
+    "GREATER_EQ",
+];
 
 pub fn find_label_definition<'a>(index: &'a Index, label_name: &str) -> Option<&'a ByteRange> {
     index.label_defs.get(label_name)
 }
 
+// Names of the labels defined in a program, in source order
+pub fn label_names(index: &Index) -> Vec<&str> {
+    let mut defs: Vec<(&String, &ByteRange)> = index.label_defs.iter().collect();
+    defs.sort_by_key(|(_, range)| range.start);
+    defs.into_iter().map(|(name, _)| name.as_str()).collect()
+}
+
 pub fn find_label_references<'a>(index: &'a Index, label_name: &str) -> Option<&'a Vec<ByteRange>> {
     index.label_refs.get(label_name)
 }
@@ -18,6 +51,16 @@ pub fn make_hover(msg: &str, range: ByteRange, doc: &Doc) -> Hover {
     }
 }
 
+pub fn make_markdown_hover(markdown: &str, range: ByteRange, doc: &Doc) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: markdown.to_string(),
+        }),
+        range: Some(labeldef_to_range(&range, doc)),
+    }
+}
+
 pub fn leading_word_range(doc_text: &str, node: Node) -> ByteRange {
     let bytes = doc_text.as_bytes();
     let start = node.start_byte();
@@ -32,3 +75,46 @@ pub fn leading_word_range(doc_text: &str, node: Node) -> ByteRange {
 
     ByteRange { start, end }
 }
+
+// Byte range of the label block containing `offset`: from its LILY up to the next LILY (or EOF)
+pub fn label_block_range(index: &Index, doc_text: &str, offset: usize) -> Option<ByteRange> {
+    let mut starts: Vec<usize> = index.label_defs.values().map(|r| r.start).collect();
+    starts.sort_unstable();
+
+    let i = starts.partition_point(|&s| s <= offset);
+    if i == 0 {
+        return None;
+    }
+    let start = starts[i - 1];
+    let next = starts.get(i).copied().unwrap_or(doc_text.len());
+    let end = start + doc_text[start..next].trim_end().len();
+
+    Some(ByteRange { start, end })
+}
+
+// Innermost instruction node of a `statement` (e.g. `dup`, `hop`, `label_definition`)
+pub fn statement_instruction(statement: Node) -> Option<Node> {
+    let mut cur = statement;
+    while matches!(
+        cur.kind(),
+        "statement"
+            | "stack_operation"
+            | "control_flow"
+            | "stack_manipulation"
+            | "arithmetic"
+            | "comparison"
+    ) {
+        cur = cur.named_child(0)?;
+    }
+    Some(cur)
+}
+
+// Top-level instructions in source order, skipping comments and ERROR nodes
+pub fn program_instructions(tree: &Tree) -> Vec<Node<'_>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|n| n.kind() == "statement")
+        .filter_map(statement_instruction)
+        .collect()
+}
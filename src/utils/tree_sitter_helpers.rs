@@ -21,7 +21,7 @@ pub fn find_node_at_position<'tree>(
             line: line_col.line,
             col: 0,
         })
-        .map(|ts| usize::from(ts))
+        .map(usize::from)
         .unwrap_or(0);
     let column_bytes = offset - line_start_offset;
 
@@ -35,6 +35,13 @@ pub fn find_node_at_position<'tree>(
         .unwrap_or_else(|| tree.root_node())
 }
 
+pub fn node_byte_range(node: Node) -> ByteRange {
+    ByteRange {
+        start: node.start_byte(),
+        end: node.end_byte(),
+    }
+}
+
 pub fn labeldef_to_range(def: &ByteRange, doc: &Doc) -> Range {
     let start_offset = def.start;
     let end_offset = def.end;
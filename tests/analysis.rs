@@ -0,0 +1,120 @@
+mod common;
+
+use std::collections::HashMap;
+use std::ptr;
+
+use common::{countdown, doc};
+use lsp_froggy::analysis::stack::BlockEffect;
+use lsp_froggy::lint::{LintConfig, RuleLevel};
+use tower_lsp::lsp_types::NumberOrString;
+
+#[test]
+fn cfg_follows_jumps() {
+    let doc = countdown();
+    let cfg = doc.cfg();
+    let kinds: Vec<_> = cfg.nodes.iter().map(|n| n.kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            "plop",
+            "label_definition",
+            "ribbit",
+            "burp",
+            "dup",
+            "leap",
+            "hop",
+            "label_definition",
+            "splash"
+        ]
+    );
+    assert_eq!(cfg.nodes[5].succs, [6, 7]);
+    assert_eq!(cfg.nodes[6].succs, [1]);
+    assert!(cfg.nodes[8].succs.is_empty());
+}
+
+#[test]
+fn block_effects_are_inferred_per_label() {
+    let doc = countdown();
+    let blocks = &doc.stack_analysis().blocks;
+    assert_eq!(
+        blocks["loop"],
+        BlockEffect {
+            consumes: 1,
+            net: Some(0)
+        }
+    );
+    assert_eq!(
+        blocks["done"],
+        BlockEffect {
+            consumes: 1,
+            net: Some(-1)
+        }
+    );
+
+    // Leaving through the LEAP has one more value on the stack than falling through
+    let uneven = self::doc("LILY top\nPLOP 1\nLEAP top\nSPLASH\n");
+    assert_eq!(uneven.stack_analysis().blocks["top"].net, None);
+}
+
+#[test]
+fn queries_are_computed_once_per_snapshot() {
+    let doc = countdown();
+    assert!(ptr::eq(doc.index(), doc.index()));
+    assert!(ptr::eq(doc.cfg(), doc.cfg()));
+    assert!(ptr::eq(doc.stack_analysis(), doc.stack_analysis()));
+
+    let redundant = self::doc("PLOP 1\nDUP\nSPLASH\n");
+    let quiet = LintConfig::from(HashMap::from([(
+        "redundant-stack-op".to_string(),
+        RuleLevel::Off,
+    )]));
+    let default = redundant.diagnostics(&LintConfig::default());
+    assert_eq!(default.len(), 1);
+    assert_eq!(redundant.diagnostics(&LintConfig::default()), default);
+    // A different config is a different input
+    assert!(redundant.diagnostics(&quiet).is_empty());
+}
+
+#[test]
+fn stack_effect_annotations_are_checked() {
+    let mismatches = |text: &str| -> Vec<String> {
+        self::doc(text)
+            .diagnostics(&LintConfig::default())
+            .into_iter()
+            .filter(|d| d.code == Some(NumberOrString::String("stack-effect-mismatch".into())))
+            .map(|d| d.message)
+            .collect()
+    };
+
+    let countdown = "PLOP 3\n// ( n -- n )\nLILY loop\nBURP\nDUP\nLEAP done\nHOP loop\n\
+                     // ( n -- )\nLILY done\nSPLASH\n";
+    let doc = doc(countdown);
+    assert_eq!(doc.index().stack_effects["loop"].to_string(), "( n -- n )");
+    assert_eq!(doc.index().stack_effects["done"].to_string(), "( n -- )");
+    assert!(mismatches(countdown).is_empty());
+
+    assert_eq!(
+        mismatches("PLOP 1\nPLOP 2\n// ( a b -- sum )\nLILY sum\nADD\nDUP\nRIBBIT\n"),
+        ["`sum` is declared `( a b -- sum )`, but takes 2 values and leaves 2 values"]
+    );
+    assert_eq!(
+        mismatches("PLOP 0\n// ( flag -- )\nLILY test\nLEAP end\nPLOP 1\nLILY end\n"),
+        [
+            "`test` is declared `( flag -- )`, but leaves a different number of values \
+             depending on where it exits"
+        ]
+    );
+    // Only a comment on the line right above counts
+    let apart = self::doc("// ( a -- )\n\nLILY start\nPLOP 1 // ( -- x )\nLILY next\n");
+    assert!(apart.index().stack_effects.is_empty());
+}
+
+#[test]
+fn comments_above_labels_are_their_docs() {
+    let doc = doc("// Not about any label\n\n\
+                   // Counts down to zero.\n//\n// Prints every value.\n// ( n -- n )\nLILY loop\n\
+                   HOP loop // trailing\n// froggy-ignore: unused-label\nLILY done\n");
+    let docs = &doc.index().label_docs;
+    assert_eq!(docs["loop"], "Counts down to zero.\n\nPrints every value.");
+    assert!(!docs.contains_key("done"));
+}
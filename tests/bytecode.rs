@@ -0,0 +1,71 @@
+mod common;
+
+use common::{doc, fixture};
+use lsp_froggy::bytecode::{DecodeError, Instr, Program, disassemble};
+use lsp_froggy::compiler::compile;
+
+#[test]
+fn jumps_are_resolved_to_code_offsets() {
+    let program = compile(&doc(&fixture("countdown.frog"))).unwrap();
+    let instrs: Vec<Instr> = program
+        .instructions()
+        .unwrap()
+        .into_iter()
+        .map(|(_, i)| i)
+        .collect();
+
+    assert_eq!(
+        instrs,
+        [
+            Instr::PushInt(3),
+            Instr::Print, // loop
+            Instr::Dec,
+            Instr::Dup,
+            Instr::JumpIfZero(0x16),
+            Instr::Jump(0x09),
+            Instr::Pop, // done
+        ]
+    );
+    assert_eq!(program.line_of(0x09), Some(4));
+}
+
+#[test]
+fn programs_round_trip_through_bytes() {
+    let program = compile(&doc("PLOP \"hi\\n\"\nPLOP 1.5\nPLOP \"hi\\n\"\nRIBBIT\n")).unwrap();
+    assert_eq!(program.strings, ["hi\n"]);
+
+    let bytes = program.to_bytes();
+    assert_eq!(&bytes[..4], b"FROG");
+    assert_eq!(Program::from_bytes(&bytes).unwrap(), program);
+
+    assert_eq!(
+        Program::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(DecodeError::Truncated)
+    );
+    assert_eq!(Program::from_bytes(b"NOPE"), Err(DecodeError::BadMagic));
+}
+
+#[test]
+fn errors_stop_compilation() {
+    let errors = compile(&doc("HOP nowhere\nPLOP 99999999999999999999\n")).unwrap_err();
+    let messages: Vec<_> = errors.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "undefined label `nowhere`",
+            "`99999999999999999999` does not fit in 64 bits"
+        ]
+    );
+
+    assert!(compile(&doc(&fixture("syntax_error.frog"))).is_err());
+}
+
+#[test]
+fn disassembly_shows_source_lines() {
+    let text = fixture("countdown.frog");
+    let program = compile(&doc(&text)).unwrap();
+    let listing = disassemble(&program, Some(&text)).unwrap();
+
+    assert!(listing.starts_with("0x0000  push_int     3"));
+    assert!(listing.contains("jump_if_zero 0x0016       ; line 7: LEAP done"));
+}
@@ -0,0 +1,115 @@
+use std::process::{Command, Output};
+
+use serde_json::Value;
+
+// `froggy check <args>`, run from the repository root so paths print as given
+fn check(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_froggy"))
+        .arg("check")
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap()
+}
+
+fn stdout_json(output: &Output) -> Value {
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
+const BROKEN: &str = "tests/fixtures/syntax_error.frog";
+const LINTED: &str = "tests/fixtures/lints.frog";
+const SUPPRESSED: &str = "tests/fixtures/suppressed.frog";
+
+#[test]
+fn errors_fail_the_check() {
+    let output = check(&[BROKEN, LINTED]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains(
+        "error: Mnemonics are uppercase: `ribbit` must be written as `RIBBIT`\n --> tests/fixtures/syntax_error.frog:2:1\n"
+    ));
+    assert!(stdout.contains("warning[gulp-burp-noop]: `GULP BURP` has no effect\n"));
+    assert!(stdout.ends_with("2 file(s) checked: 1 error(s), 4 warning(s)\n"));
+}
+
+#[test]
+fn warnings_alone_pass_the_check() {
+    let output = check(&["--format", "json", LINTED]);
+    assert_eq!(output.status.code(), Some(0));
+
+    let diagnostics = stdout_json(&output);
+    let codes: Vec<&str> = diagnostics
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|d| d["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            "redundant-stack-op",
+            "gulp-burp-noop",
+            "unreachable-code",
+            "unentered-label"
+        ]
+    );
+}
+
+#[test]
+fn sarif_lists_results_and_describes_their_rules() {
+    let output = check(&["--format", "sarif", BROKEN, SUPPRESSED]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let sarif = stdout_json(&output);
+    let run = &sarif["runs"][0];
+    let results: Vec<(&str, &str, &str, u64)> = run["results"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            let location = &r["locations"][0]["physicalLocation"];
+            (
+                r["ruleId"].as_str().unwrap(),
+                r["level"].as_str().unwrap(),
+                location["artifactLocation"]["uri"].as_str().unwrap(),
+                location["region"]["startLine"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        results,
+        [
+            ("syntax-error", "error", BROKEN, 2),
+            ("unused-label", "note", BROKEN, 3),
+            ("unused-suppression", "warning", SUPPRESSED, 6),
+        ]
+    );
+
+    let rules = run["tool"]["driver"]["rules"].as_array().unwrap();
+    let rule = |id: &str| {
+        rules
+            .iter()
+            .find(|r| r["id"] == id)
+            .unwrap_or_else(|| panic!("no rule descriptor for `{id}`"))
+    };
+    for (id, ..) in &results {
+        assert!(rule(id)["shortDescription"]["text"].is_string());
+    }
+    assert_eq!(
+        rule("unused-suppression")["shortDescription"]["text"],
+        "froggy-ignore comment silences nothing"
+    );
+    assert_eq!(
+        rule("unused-suppression")["defaultConfiguration"]["level"],
+        "warning"
+    );
+    assert_eq!(
+        rule("mixed-case")["defaultConfiguration"]["level"],
+        "warning"
+    );
+    assert_eq!(
+        rule("unused-label")["defaultConfiguration"]["level"],
+        "note"
+    );
+}
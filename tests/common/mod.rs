@@ -0,0 +1,239 @@
+// Shared by every test crate, each of which uses only some of it
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use lsp_froggy::backend::{Backend, BackendService};
+use lsp_froggy::document::Doc;
+use lsp_froggy::logging::Logger;
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+use tower::{Service, ServiceExt};
+use tower_lsp::jsonrpc::{Request, Response};
+use tower_lsp::lsp_types::{Diagnostic, PublishDiagnosticsParams, Url};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn fixture(name: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("read {}: {e}", path.display()))
+}
+
+pub fn doc(text: &str) -> Doc {
+    versioned_doc(text, 0)
+}
+
+pub fn versioned_doc(text: &str, version: i32) -> Doc {
+    Doc::parse(text.to_string(), version, &AtomicBool::new(false)).unwrap()
+}
+
+pub fn countdown() -> Doc {
+    doc(&fixture("countdown.frog"))
+}
+
+pub fn uri(name: &str) -> Url {
+    Url::parse(&format!("file:///fixtures/{name}")).unwrap()
+}
+
+// Position of the `nth` occurrence of `needle` in `text`, as LSP JSON
+pub fn position_of(text: &str, needle: &str, nth: usize) -> Value {
+    let offset = text
+        .match_indices(needle)
+        .nth(nth)
+        .unwrap_or_else(|| panic!("`{needle}` #{nth} not found"))
+        .0;
+    let before = &text[..offset];
+    let line = before.matches('\n').count();
+    let character = before.len() - before.rfind('\n').map_or(0, |i| i + 1);
+    json!({ "line": line, "character": character })
+}
+
+// A Backend running in-process, with a fake client on the other end of its socket
+pub struct TestServer {
+    service: BackendService,
+    next_id: i64,
+    // Notifications the server sent, not yet consumed by a test
+    notifications: Vec<Request>,
+    incoming: mpsc::UnboundedReceiver<Request>,
+    // Reply to `workspace/configuration`, one copy per requested item
+    pub configuration: Arc<Mutex<Value>>,
+    // Methods of every server-to-client request, in order
+    pub client_requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl TestServer {
+    pub fn new() -> Self {
+        let (service, socket) = Backend::service(Logger::default());
+        let (mut requests, mut responses) = socket.split();
+        let (tx, incoming) = mpsc::unbounded_channel();
+        let configuration = Arc::new(Mutex::new(Value::Null));
+        let client_requests = Arc::new(Mutex::new(Vec::new()));
+
+        let config = configuration.clone();
+        let seen = client_requests.clone();
+        tokio::spawn(async move {
+            while let Some(req) = requests.next().await {
+                let Some(id) = req.id().cloned() else {
+                    let _ = tx.send(req);
+                    continue;
+                };
+
+                seen.lock().unwrap().push(req.method().to_string());
+                let result = match req.method() {
+                    "workspace/configuration" => {
+                        let items = req
+                            .params()
+                            .and_then(|p| p["items"].as_array())
+                            .map_or(0, Vec::len);
+                        Value::Array(vec![config.lock().unwrap().clone(); items])
+                    }
+                    _ => Value::Null,
+                };
+                if responses.send(Response::from_ok(id, result)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            service,
+            next_id: 1,
+            notifications: Vec::new(),
+            incoming,
+            configuration,
+            client_requests,
+        }
+    }
+
+    async fn call(&mut self, req: Request) -> Option<Response> {
+        let fut = self.service.ready().await.unwrap().call(req);
+        tokio::time::timeout(TIMEOUT, fut)
+            .await
+            .expect("server did not answer in time")
+            .unwrap()
+    }
+
+    // Send a request and return its result, panicking on an error response
+    pub async fn request(&mut self, method: &'static str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut req = Request::build(method).id(id);
+        if !params.is_null() {
+            req = req.params(params);
+        }
+        let req = req.finish();
+        let response = self.call(req).await.expect("request got no response");
+        match response.into_parts() {
+            (_, Ok(result)) => result,
+            (_, Err(e)) => panic!("{method} failed: {e}"),
+        }
+    }
+
+    // Start a request or notification without waiting for it, so several can be in flight
+    pub async fn send(
+        &mut self,
+        method: &'static str,
+        params: Value,
+        id: Option<i64>,
+    ) -> impl Future<Output = Option<Response>> + use<> {
+        let mut req = Request::build(method).params(params);
+        if let Some(id) = id {
+            req = req.id(id);
+        }
+        let fut = self.service.ready().await.unwrap().call(req.finish());
+        async move {
+            tokio::time::timeout(TIMEOUT, fut)
+                .await
+                .expect("server did not answer in time")
+                .unwrap()
+        }
+    }
+
+    pub async fn notify(&mut self, method: &'static str, params: Value) {
+        let req = Request::build(method).params(params).finish();
+        assert!(self.call(req).await.is_none());
+    }
+
+    pub async fn initialize(&mut self, params: Value) -> Value {
+        let result = self.request("initialize", params).await;
+        self.notify("initialized", json!({})).await;
+        result
+    }
+
+    pub async fn open(&mut self, uri: &Url, text: &str) {
+        self.open_as(uri, "froggy", text).await;
+    }
+
+    pub async fn open_as(&mut self, uri: &Url, language: &str, text: &str) {
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": { "uri": uri, "languageId": language, "version": 1, "text": text }
+            }),
+        )
+        .await;
+    }
+
+    pub async fn change(&mut self, uri: &Url, version: i32, text: &str) {
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": version },
+                "contentChanges": [{ "text": text }]
+            }),
+        )
+        .await;
+    }
+
+    // Next notification with `method` satisfying `pred`, waiting for it if necessary.
+    // Earlier non-matching notifications are kept for later calls.
+    pub async fn notification(&mut self, method: &str, pred: impl Fn(&Value) -> bool) -> Value {
+        let matches = |n: &Request| n.method() == method && n.params().is_some_and(&pred);
+
+        if let Some(i) = self.notifications.iter().position(matches) {
+            let n = self.notifications.remove(i);
+            return n.params().cloned().unwrap_or_default();
+        }
+
+        loop {
+            let n = tokio::time::timeout(TIMEOUT, self.incoming.recv())
+                .await
+                .unwrap_or_else(|_| panic!("no {method} notification arrived"))
+                .expect("server closed its socket");
+            if matches(&n) {
+                return n.params().cloned().unwrap_or_default();
+            }
+            self.notifications.push(n);
+        }
+    }
+
+    pub async fn diagnostics(&mut self, uri: &Url) -> Vec<Diagnostic> {
+        let params = self
+            .notification("textDocument/publishDiagnostics", |p| {
+                p["uri"] == uri.as_str()
+            })
+            .await;
+        serde_json::from_value::<PublishDiagnosticsParams>(params)
+            .unwrap()
+            .diagnostics
+    }
+}
+
+pub fn codes(diags: &[Diagnostic]) -> Vec<String> {
+    let mut codes: Vec<String> = diags
+        .iter()
+        .map(|d| match &d.code {
+            Some(tower_lsp::lsp_types::NumberOrString::String(s)) => s.clone(),
+            _ => "syntax".to_string(),
+        })
+        .collect();
+    codes.sort();
+    codes
+}
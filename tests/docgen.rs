@@ -0,0 +1,71 @@
+mod common;
+
+use common::{countdown, doc};
+use lsp_froggy::docgen::{Format, LabelInfo, file_summary, highlight, label_infos, render_page};
+
+#[test]
+fn labels_are_described_with_their_jumps_and_effects() {
+    let doc = countdown();
+    assert_eq!(
+        file_summary(&doc).as_deref(),
+        Some("Count down from three, printing each value")
+    );
+    assert_eq!(
+        label_infos(&doc),
+        [
+            LabelInfo {
+                name: "loop".to_string(),
+                line: 3,
+                docs: None,
+                declared: None,
+                inferred: "( 1 -- 1 )".to_string(),
+                jumps_to: vec!["done".to_string(), "loop".to_string()],
+                jumped_from: vec![8],
+            },
+            LabelInfo {
+                name: "done".to_string(),
+                line: 9,
+                docs: None,
+                declared: None,
+                inferred: "( 1 -- 0 )".to_string(),
+                jumps_to: vec![],
+                jumped_from: vec![7],
+            },
+        ]
+    );
+
+    // Comments right above the first label are its docs, not the file's
+    let documented = self::doc("// Adds.\n// ( a b -- sum )\nLILY add\nADD\n");
+    assert_eq!(file_summary(&documented), None);
+    let info = &label_infos(&documented)[0];
+    assert_eq!(info.docs.as_deref(), Some("Adds."));
+    assert_eq!(info.declared.as_deref(), Some("( a b -- sum )"));
+}
+
+#[test]
+fn source_is_highlighted_like_semantic_tokens() {
+    let doc = doc("PLOP \"<a>\"\nLILY top\n");
+    assert_eq!(
+        highlight(&doc),
+        "<pre class=\"source\"><code>\
+         <span class=\"line\" id=\"L1\"><span class=\"tok-function\">PLOP</span> \
+         <span class=\"tok-string\">&quot;&lt;a&gt;&quot;</span></span>\n\
+         <span class=\"line\" id=\"L2\"><span class=\"tok-keyword\">LILY</span> \
+         <span class=\"tok-variable definition\">top</span></span>\n\
+         </code></pre>"
+    );
+}
+
+#[test]
+fn pages_link_back_to_the_index() {
+    let doc = countdown();
+    let html = render_page("countdown.frog", "../index.html", &doc, Format::Html);
+    assert!(html.contains("<a href=\"../index.html\">Index</a>"));
+    assert!(html.contains("<tr id=\"label-loop\">"));
+    assert!(html.contains("<a href=\"#L8\">line 8</a>"));
+
+    let md = render_page("countdown.frog", "index.md", &doc, Format::Markdown);
+    assert!(md.starts_with("[Index](index.md)\n\n# countdown.frog\n"));
+    assert!(md.contains("| `loop` (line 3) |  |  | `( 1 -- 1 )` | `done`, `loop` | line 8 |"));
+    assert!(md.ends_with("```froggy\n// Count down from three, printing each value\nPLOP 3\nLILY loop\nRIBBIT\nBURP\nDUP\nLEAP done\nHOP loop\nLILY done\nSPLASH\n```\n"));
+}
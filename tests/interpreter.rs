@@ -0,0 +1,111 @@
+mod common;
+
+use std::collections::VecDeque;
+
+use common::{doc, fixture};
+use lsp_froggy::interpreter::{Interpreter, Io, Status, Value};
+
+#[derive(Default)]
+struct Recorder {
+    input: VecDeque<String>,
+    printed: Vec<String>,
+}
+
+impl Io for Recorder {
+    fn print(&mut self, value: &Value) {
+        self.printed.push(value.to_string());
+    }
+
+    fn read(&mut self) -> Option<String> {
+        self.input.pop_front()
+    }
+}
+
+// Enter `pieces` one after another, running after each as the REPL does
+fn enter(interpreter: &mut Interpreter, io: &mut Recorder, pieces: &[&str]) -> Status {
+    let mut status = interpreter.status();
+    for piece in pieces {
+        interpreter.load(&doc(piece)).unwrap();
+        status = interpreter.run(1000, io).unwrap();
+    }
+    status
+}
+
+#[test]
+fn programs_run_to_completion() {
+    let mut interpreter = Interpreter::new();
+    let mut io = Recorder::default();
+    let status = enter(&mut interpreter, &mut io, &[&fixture("countdown.frog")]);
+
+    assert_eq!(status, Status::Done);
+    assert_eq!(io.printed, ["3", "2", "1"]);
+    assert!(interpreter.stack().is_empty());
+}
+
+#[test]
+fn labels_carry_across_lines() {
+    let mut interpreter = Interpreter::new();
+    let mut io = Recorder::default();
+
+    // A loop entered line by line runs again on every HOP back
+    let status = enter(
+        &mut interpreter,
+        &mut io,
+        &["PLOP 2", "LILY loop", "RIBBIT", "BURP", "DUP", "LEAP done"],
+    );
+    assert_eq!(status, Status::Done);
+    assert_eq!(
+        enter(&mut interpreter, &mut io, &["HOP loop"]),
+        Status::Waiting("done".into())
+    );
+    assert_eq!(io.printed, ["2", "1"]);
+
+    // Lines between a jump and its label are skipped
+    let status = enter(&mut interpreter, &mut io, &["PLOP 99", "LILY done"]);
+    assert_eq!(status, Status::Done);
+    assert_eq!(interpreter.stack(), [Value::Int(0)]);
+
+    io.input.push_back("4.5".into());
+    assert_eq!(enter(&mut interpreter, &mut io, &["CROAK"]), Status::Done);
+    assert_eq!(interpreter.stack(), [Value::Int(0), Value::Float(4.5)]);
+}
+
+#[test]
+fn runtime_errors_leave_the_stack_as_it_was() {
+    let mut interpreter = Interpreter::new();
+    let mut io = Recorder::default();
+    interpreter
+        .load(&doc("PLOP 1\nPLOP \"a\"\nSUB\nPLOP 2\n"))
+        .unwrap();
+
+    let error = interpreter.run(1000, &mut io).unwrap_err();
+    assert_eq!(error.message, "`SUB` cannot be applied to a string");
+    assert_eq!(interpreter.stack(), [Value::Int(1), Value::Str("a".into())]);
+    // The rest of the program is dropped
+    assert_eq!(interpreter.status(), Status::Done);
+
+    interpreter.load(&doc("SPLASH\nSPLASH\nADD\n")).unwrap();
+    let error = interpreter.run(1000, &mut io).unwrap_err();
+    assert_eq!(error.message, "`ADD` needs 2 values, but the stack has 0");
+
+    // Floats divide by zero no more than integers do
+    for program in ["PLOP 1\nPLOP 0\nDIV\n", "PLOP 1.5\nPLOP 0.0\nDIV\n"] {
+        let mut interpreter = Interpreter::new();
+        interpreter.load(&doc(program)).unwrap();
+        let error = interpreter.run(1000, &mut io).unwrap_err();
+        assert_eq!(error.message, "Division by zero");
+        assert_eq!(interpreter.stack().len(), 2);
+    }
+}
+
+#[test]
+fn endless_loops_pause_at_the_budget() {
+    let mut interpreter = Interpreter::new();
+    let mut io = Recorder::default();
+    interpreter.load(&doc("LILY top\nHOP top\n")).unwrap();
+
+    assert_eq!(interpreter.run(10, &mut io).unwrap(), Status::Ready);
+    assert_eq!(interpreter.next_instruction(), Some("HOP top"));
+    interpreter.step(&mut io).unwrap();
+    assert_eq!(interpreter.status(), Status::Ready);
+}
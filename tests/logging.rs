@@ -0,0 +1,17 @@
+use lsp_froggy::logging::{LogFilter, LogLevel};
+
+#[test]
+fn filter_applies_per_target_levels() {
+    let filter = LogFilter::parse("warning, documents=trace").unwrap();
+
+    assert!(filter.enabled("server", LogLevel::Error));
+    assert!(!filter.enabled("server", LogLevel::Info));
+    assert!(filter.enabled("documents", LogLevel::Trace));
+    assert!(!filter.enabled("documents", LogLevel::Off));
+}
+
+#[test]
+fn filter_rejects_unknown_levels() {
+    assert!(LogFilter::parse("loud").is_err());
+    assert!(LogFilter::parse("documents=loud").is_err());
+}
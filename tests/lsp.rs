@@ -0,0 +1,1184 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{TestServer, codes, fixture, position_of, uri};
+use serde_json::{Value, json};
+use tower_lsp::lsp_types::*;
+
+async fn server_with(name: &str) -> (TestServer, Url, String) {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri(name);
+    let text = fixture(name);
+    server.open(&uri, &text).await;
+    (server, uri, text)
+}
+
+fn at(uri: &Url, text: &str, needle: &str, nth: usize) -> Value {
+    json!({
+        "textDocument": { "uri": uri },
+        "position": position_of(text, needle, nth),
+    })
+}
+
+#[tokio::test]
+async fn initialize_advertises_capabilities() {
+    let mut server = TestServer::new();
+    let result = server.initialize(json!({ "capabilities": {} })).await;
+    let caps: ServerCapabilities = serde_json::from_value(result["capabilities"].clone()).unwrap();
+
+    assert!(caps.hover_provider.is_some());
+    assert!(caps.definition_provider.is_some());
+    assert!(caps.references_provider.is_some());
+    assert!(caps.document_symbol_provider.is_some());
+    assert!(caps.selection_range_provider.is_some());
+    assert!(caps.semantic_tokens_provider.is_some());
+
+    let Some(TextDocumentSyncCapability::Options(sync)) = caps.text_document_sync else {
+        panic!("expected text document sync options");
+    };
+    assert_eq!(sync.open_close, Some(true));
+    assert_eq!(
+        sync.save,
+        Some(TextDocumentSyncSaveOptions::SaveOptions(SaveOptions {
+            include_text: Some(true)
+        }))
+    );
+}
+
+#[tokio::test]
+async fn clean_file_has_no_diagnostics() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+}
+
+#[tokio::test]
+async fn syntax_errors_are_published() {
+    let (mut server, uri, _) = server_with("syntax_error.frog").await;
+    let diags = server.diagnostics(&uri).await;
+
+    let syntax: Vec<_> = diags.iter().filter(|d| d.code.is_none()).collect();
+    assert!(!syntax.is_empty());
+    assert!(syntax.iter().all(|d| d.range.start.line == 1));
+    assert!(
+        syntax
+            .iter()
+            .all(|d| d.severity == Some(DiagnosticSeverity::ERROR))
+    );
+}
+
+#[tokio::test]
+async fn lints_are_published() {
+    let (mut server, uri, _) = server_with("lints.frog").await;
+    let diags = server.diagnostics(&uri).await;
+
+    assert_eq!(
+        codes(&diags),
+        [
+            "gulp-burp-noop",
+            "redundant-stack-op",
+            "unentered-label",
+            "unreachable-code"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn suppression_comments_silence_lints() {
+    let (mut server, uri, _) = server_with("suppressed.frog").await;
+    let diags = server.diagnostics(&uri).await;
+
+    assert_eq!(codes(&diags), ["unused-suppression"]);
+    assert_eq!(diags[0].range.start.line, 5);
+}
+
+#[tokio::test]
+async fn initialization_options_configure_lints() {
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({
+            "capabilities": {},
+            "initializationOptions": {
+                // A bad level is reported and skipped, leaving the other rules configured
+                "lints": {
+                    "unreachable-code": "off",
+                    "unentered-label": "error",
+                    "mixed-case": "loud"
+                },
+                // And so is a bad log level, leaving the lints configured
+                "logLevel": "chatty"
+            }
+        }))
+        .await;
+    let problem = server
+        .notification("window/logMessage", |p| {
+            p["message"]
+                .as_str()
+                .is_some_and(|m| m.contains("for `logLevel`; keeping the current level"))
+        })
+        .await;
+    assert_eq!(problem["type"], 2);
+    let problem = server
+        .notification("window/logMessage", |p| {
+            p["message"]
+                .as_str()
+                .is_some_and(|m| m.contains("invalid lint level `loud` for lint rule `mixed-case`"))
+        })
+        .await;
+    assert_eq!(problem["type"], 2);
+    let uri = uri("lints.frog");
+    server.open(&uri, &fixture("lints.frog")).await;
+    let diags = server.diagnostics(&uri).await;
+
+    assert_eq!(
+        codes(&diags),
+        ["gulp-burp-noop", "redundant-stack-op", "unentered-label"]
+    );
+    let unentered = diags.iter().find(|d| d.range.start.line == 6).unwrap();
+    assert_eq!(unentered.severity, Some(DiagnosticSeverity::ERROR));
+}
+
+#[tokio::test]
+async fn configuration_change_republishes_diagnostics() {
+    let (mut server, uri, _) = server_with("lints.frog").await;
+    server.diagnostics(&uri).await;
+
+    server
+        .notify(
+            "workspace/didChangeConfiguration",
+            json!({ "settings": { "froggy": { "lints": { "redundant-stack-op": "off" } } } }),
+        )
+        .await;
+    let diags = server.diagnostics(&uri).await;
+
+    assert!(!codes(&diags).contains(&"redundant-stack-op".to_string()));
+}
+
+#[tokio::test]
+async fn configuration_is_pulled_when_supported() {
+    let mut server = TestServer::new();
+    *server.configuration.lock().unwrap() = json!({ "lints": { "gulp-burp-noop": "off" } });
+    server
+        .initialize(json!({ "capabilities": { "workspace": { "configuration": true } } }))
+        .await;
+    let uri = uri("lints.frog");
+    server.open(&uri, &fixture("lints.frog")).await;
+    let diags = server.diagnostics(&uri).await;
+
+    assert!(
+        server
+            .client_requests
+            .lock()
+            .unwrap()
+            .contains(&"workspace/configuration".to_string())
+    );
+    assert!(!codes(&diags).contains(&"gulp-burp-noop".to_string()));
+}
+
+#[tokio::test]
+async fn did_change_replaces_text_and_diagnostics() {
+    let (mut server, uri, _) = server_with("syntax_error.frog").await;
+    assert!(!server.diagnostics(&uri).await.is_empty());
+
+    server.change(&uri, 2, "PLOP 1\nRIBBIT\n").await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    let hover = server
+        .request(
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 2 } }),
+        )
+        .await;
+    assert_eq!(hover["contents"], "RIBBIT: Print top of stack");
+}
+
+#[tokio::test]
+async fn hover_describes_mnemonics() {
+    let (mut server, uri, text) = server_with("countdown.frog").await;
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, &text, "BURP", 0))
+        .await;
+    assert_eq!(hover["contents"], "BURP: Decrement top of stack");
+    assert_eq!(hover["range"]["start"], position_of(&text, "BURP", 0));
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, &text, "LEAP", 0))
+        .await;
+    assert_eq!(
+        hover["contents"],
+        "LEAP <Lilypad>: Pop a, if (a == 0) then jump to lilypad"
+    );
+}
+
+#[tokio::test]
+async fn hover_on_label_definition() {
+    let (mut server, uri, text) = server_with("countdown.frog").await;
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, &text, "loop", 0))
+        .await;
+    assert_eq!(hover["contents"], "Label definition: loop");
+}
+
+#[tokio::test]
+async fn hover_on_whitespace_is_empty() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+
+    let hover = server
+        .request(
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 10, "character": 0 } }),
+        )
+        .await;
+    assert!(hover.is_null());
+}
+
+#[tokio::test]
+async fn goto_definition_resolves_label() {
+    let (mut server, uri, text) = server_with("countdown.frog").await;
+
+    // `HOP loop` -> `LILY loop`
+    let def = server
+        .request("textDocument/definition", at(&uri, &text, "loop", 1))
+        .await;
+    let def: Location = serde_json::from_value(def).unwrap();
+    assert_eq!(def.uri, uri);
+    assert_eq!(
+        def.range.start,
+        serde_json::from_value(position_of(&text, "LILY loop", 0)).unwrap()
+    );
+
+    // Not on a jump target
+    let none = server
+        .request("textDocument/definition", at(&uri, &text, "RIBBIT", 0))
+        .await;
+    assert!(none.is_null());
+}
+
+#[tokio::test]
+async fn references_find_jumps() {
+    let (mut server, uri, text) = server_with("countdown.frog").await;
+
+    let mut params = at(&uri, &text, "done", 0);
+    params["context"] = json!({ "includeDeclaration": true });
+    let refs: Vec<Location> =
+        serde_json::from_value(server.request("textDocument/references", params).await).unwrap();
+    assert_eq!(refs.len(), 2);
+    assert_eq!(refs[0].range.start.line, 8);
+    assert_eq!(refs[1].range.start.line, 6);
+
+    let mut params = at(&uri, &text, "done", 0);
+    params["context"] = json!({ "includeDeclaration": false });
+    let refs: Vec<Location> =
+        serde_json::from_value(server.request("textDocument/references", params).await).unwrap();
+    assert_eq!(refs.len(), 1);
+}
+
+#[tokio::test]
+async fn document_symbols_list_labels() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+
+    let symbols = server
+        .request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    let symbols: Vec<DocumentSymbol> = serde_json::from_value(symbols).unwrap();
+    let mut names: Vec<_> = symbols.iter().map(|s| s.name.as_str()).collect();
+    names.sort();
+    assert_eq!(names, ["done", "loop"]);
+    assert!(symbols.iter().all(|s| s.kind == SymbolKind::FUNCTION));
+}
+
+#[tokio::test]
+async fn semantic_tokens_classify_source() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+
+    let tokens = server
+        .request(
+            "textDocument/semanticTokens/full",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    let tokens: SemanticTokens = serde_json::from_value(tokens).unwrap();
+
+    // Decode into absolute (line, col, len, type)
+    let (mut line, mut col) = (0, 0);
+    let decoded: Vec<(u32, u32, u32, u32)> = tokens
+        .data
+        .iter()
+        .map(|t| {
+            if t.delta_line > 0 {
+                line += t.delta_line;
+                col = t.delta_start;
+            } else {
+                col += t.delta_start;
+            }
+            (line, col, t.length, t.token_type)
+        })
+        .collect();
+
+    // PLOP (function) and 3 (number)
+    assert!(decoded.contains(&(1, 0, 4, 5)));
+    assert!(decoded.contains(&(1, 5, 1, 1)));
+    // LILY (keyword) and loop (variable)
+    assert!(decoded.contains(&(2, 0, 4, 0)));
+    assert!(decoded.contains(&(2, 5, 4, 4)));
+    // RIBBIT (I/O)
+    assert!(decoded.contains(&(3, 0, 6, 7)));
+}
+
+#[tokio::test]
+async fn selection_range_expands_outwards() {
+    let (mut server, uri, text) = server_with("countdown.frog").await;
+
+    let ranges = server
+        .request(
+            "textDocument/selectionRange",
+            json!({
+                "textDocument": { "uri": uri },
+                "positions": [position_of(&text, "loop", 1)],
+            }),
+        )
+        .await;
+    let ranges: Vec<SelectionRange> = serde_json::from_value(ranges).unwrap();
+
+    let mut chain = Vec::new();
+    let mut cur = Some(&ranges[0]);
+    while let Some(r) = cur {
+        chain.push(r.range);
+        cur = r.parent.as_deref();
+    }
+
+    let r = |sl, sc, el, ec| Range::new(Position::new(sl, sc), Position::new(el, ec));
+    assert_eq!(
+        chain,
+        [
+            r(7, 4, 7, 8),  // label name
+            r(7, 0, 7, 8),  // HOP instruction
+            r(2, 0, 7, 8),  // `loop` block
+            r(0, 0, 10, 0), // whole file
+        ]
+    );
+}
+
+#[tokio::test]
+async fn completion_returns_items() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+
+    let items = server
+        .request(
+            "textDocument/completion",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 0 } }),
+        )
+        .await;
+    assert!(items.as_array().is_some_and(|a| !a.is_empty()));
+}
+
+#[tokio::test]
+async fn requests_for_unknown_documents_return_null() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("missing.frog");
+
+    let hover = server
+        .request(
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 0, "character": 0 } }),
+        )
+        .await;
+    assert!(hover.is_null());
+}
+
+#[tokio::test]
+async fn shutdown_succeeds() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    assert!(server.request("shutdown", Value::Null).await.is_null());
+}
+
+#[tokio::test]
+async fn set_trace_enables_log_trace() {
+    let (mut server, uri, _) = server_with("syntax_error.frog").await;
+    server.diagnostics(&uri).await;
+
+    server
+        .notify("$/setTrace", json!({ "value": "verbose" }))
+        .await;
+    server.change(&uri, 2, "PLOP 1\nRIBBIT\nLEAP\n").await;
+
+    let trace = server
+        .notification("$/logTrace", |p| {
+            p["message"]
+                .as_str()
+                .is_some_and(|m| m.starts_with("diagnostics for"))
+        })
+        .await;
+    assert!(trace["verbose"].as_str().is_some_and(|v| v.contains("3:")));
+}
+
+#[tokio::test]
+async fn rapid_edits_publish_only_the_latest_version() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+    let opened = server
+        .notification("textDocument/publishDiagnostics", |_| true)
+        .await;
+    assert_eq!(opened["version"], 1);
+
+    server.change(&uri, 2, "PLOP\n").await;
+    server.change(&uri, 3, "PLOP 1\nRIBBIT\n").await;
+
+    let published = server
+        .notification("textDocument/publishDiagnostics", |_| true)
+        .await;
+    assert_eq!(published["version"], 3);
+    assert_eq!(published["diagnostics"], json!([]));
+}
+
+async fn markdown_server() -> (TestServer, Url, String) {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("guide.md");
+    let text = fixture("guide.md");
+    server.open_as(&uri, "markdown", &text).await;
+    (server, uri, text)
+}
+
+#[tokio::test]
+async fn markdown_blocks_get_diagnostics() {
+    let (mut server, uri, _) = markdown_server().await;
+    let diags = server.diagnostics(&uri).await;
+
+    // Only the broken `frog` block; the `text` block is ignored
+    assert!(!diags.is_empty());
+    assert!(diags.iter().all(|d| d.range.start.line == 22));
+}
+
+#[tokio::test]
+async fn markdown_blocks_support_hover_and_definition() {
+    let (mut server, uri, text) = markdown_server().await;
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, &text, "BURP", 0))
+        .await;
+    assert_eq!(hover["contents"], "BURP: Decrement top of stack");
+    assert_eq!(hover["range"]["start"], position_of(&text, "BURP", 0));
+
+    // Prose outside the blocks
+    let none = server
+        .request("textDocument/hover", at(&uri, &text, "Prints", 0))
+        .await;
+    assert!(none.is_null());
+
+    let def = server
+        .request("textDocument/definition", at(&uri, &text, "loop", 1))
+        .await;
+    assert_eq!(def["range"]["start"], position_of(&text, "LILY loop", 0));
+}
+
+#[tokio::test]
+async fn markdown_blocks_get_semantic_tokens() {
+    let (mut server, uri, _) = markdown_server().await;
+
+    let tokens = server
+        .request(
+            "textDocument/semanticTokens/full",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    let tokens: SemanticTokens = serde_json::from_value(tokens).unwrap();
+
+    // First token is PLOP on line 5, column 0
+    assert_eq!(tokens.data[0].delta_line, 5);
+    assert_eq!(tokens.data[0].delta_start, 0);
+    assert_eq!(tokens.data[0].length, 4);
+}
+
+fn cell(n: u32) -> Url {
+    Url::parse(&format!(
+        "vscode-notebook-cell:/fixtures/book.ipynb#cell{n}"
+    ))
+    .unwrap()
+}
+
+async fn notebook_server() -> TestServer {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let cells = [
+        // (cell kind, language, text); kind 1 is markup, 2 is code
+        (2, "froggy", "PLOP 3\nLILY loop\n"),
+        (1, "markdown", "# Notes"),
+        (2, "froggy", "RIBBIT\nBURP\nHOP loop"),
+    ];
+    server
+        .notify(
+            "notebookDocument/didOpen",
+            json!({
+                "notebookDocument": {
+                    "uri": "file:///fixtures/book.ipynb",
+                    "notebookType": "jupyter-notebook",
+                    "version": 1,
+                    "cells": cells.iter().enumerate().map(|(i, (kind, _, _))| {
+                        json!({ "kind": kind, "document": cell(i as u32) })
+                    }).collect::<Vec<_>>(),
+                },
+                "cellTextDocuments": cells.iter().enumerate().map(|(i, (_, lang, text))| {
+                    json!({ "uri": cell(i as u32), "languageId": lang, "version": 1, "text": text })
+                }).collect::<Vec<_>>(),
+            }),
+        )
+        .await;
+    server
+}
+
+#[tokio::test]
+async fn notebook_sync_is_registered_when_supported() {
+    let registrations = |server: &TestServer| {
+        server
+            .client_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| *m == "client/registerCapability")
+            .count()
+    };
+
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    assert_eq!(registrations(&server), 0);
+
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({ "capabilities": {
+            "notebookDocument": { "synchronization": { "dynamicRegistration": true } }
+        } }))
+        .await;
+    assert_eq!(registrations(&server), 1);
+}
+
+#[tokio::test]
+async fn semantic_tokens_are_refreshed_when_supported() {
+    let refreshes = |server: &TestServer| {
+        server
+            .client_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| *m == "workspace/semanticTokens/refresh")
+            .count()
+    };
+    let uri = uri("countdown.frog");
+
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    server.open(&uri, &fixture("countdown.frog")).await;
+    server.diagnostics(&uri).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(refreshes(&server), 0);
+
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({ "capabilities": {
+            "workspace": { "semanticTokens": { "refreshSupport": true } }
+        } }))
+        .await;
+    server.open(&uri, &fixture("countdown.frog")).await;
+    server.diagnostics(&uri).await;
+    // Asked for right after the diagnostics are published
+    for _ in 0..100 {
+        if refreshes(&server) > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(refreshes(&server), 1);
+}
+
+#[tokio::test]
+async fn notebook_labels_resolve_across_cells() {
+    let mut server = notebook_server().await;
+
+    // `HOP loop` in the third cell jumps to `LILY loop` in the first
+    let def = server
+        .request(
+            "textDocument/definition",
+            json!({ "textDocument": { "uri": cell(2) }, "position": { "line": 2, "character": 5 } }),
+        )
+        .await;
+    assert_eq!(def["uri"], cell(0).as_str());
+    assert_eq!(def["range"]["start"], json!({ "line": 1, "character": 0 }));
+
+    let hover = server
+        .request(
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": cell(2) }, "position": { "line": 1, "character": 1 } }),
+        )
+        .await;
+    assert_eq!(hover["contents"], "BURP: Decrement top of stack");
+    assert_eq!(
+        hover["range"]["start"],
+        json!({ "line": 1, "character": 0 })
+    );
+}
+
+#[tokio::test]
+async fn notebook_diagnostics_go_to_their_cells() {
+    let mut server = notebook_server().await;
+    // The label is used from another cell, so nothing is reported
+    assert!(server.diagnostics(&cell(0)).await.is_empty());
+    assert!(server.diagnostics(&cell(2)).await.is_empty());
+
+    server
+        .notify(
+            "notebookDocument/didChange",
+            json!({
+                "notebookDocument": { "uri": "file:///fixtures/book.ipynb", "version": 2 },
+                "change": { "cells": { "textContent": [{
+                    "document": { "uri": cell(2), "version": 2 },
+                    "changes": [{ "text": "RIBBIT\nLEAP\n" }],
+                }] } },
+            }),
+        )
+        .await;
+
+    let diags = server.diagnostics(&cell(2)).await;
+    assert!(!diags.is_empty());
+    assert!(diags.iter().all(|d| d.range.start.line == 1));
+
+    // Semantic tokens of the edited cell start at its own first line
+    let tokens = server
+        .request(
+            "textDocument/semanticTokens/full",
+            json!({ "textDocument": { "uri": cell(2) } }),
+        )
+        .await;
+    let tokens: SemanticTokens = serde_json::from_value(tokens).unwrap();
+    assert_eq!((tokens.data[0].delta_line, tokens.data[0].length), (0, 6));
+}
+
+#[tokio::test]
+async fn save_runs_compile_checks_until_the_next_edit() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("jumps.frog");
+    let text = "PLOP 0\nLEAP nowhere\n";
+    server.open(&uri, text).await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    server
+        .notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri }, "text": text }),
+        )
+        .await;
+    let diags = server.diagnostics(&uri).await;
+    assert_eq!(codes(&diags), ["compile-error"]);
+    assert_eq!(diags[0].message, "undefined label `nowhere`");
+
+    server
+        .change(&uri, 2, "PLOP 0\nLEAP nowhere\nRIBBIT\n")
+        .await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    // A save the buffer has already moved past is not checked
+    server
+        .notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri }, "text": text }),
+        )
+        .await;
+    server
+        .change(&uri, 3, "PLOP 0\nLEAP nowhere\nRIBBIT\nRIBBIT\n")
+        .await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+}
+
+#[tokio::test]
+async fn close_clears_diagnostics_outside_the_workspace() {
+    let (mut server, uri, _) = server_with("syntax_error.frog").await;
+    assert!(!server.diagnostics(&uri).await.is_empty());
+
+    server
+        .notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, "PLOP", "PLOP", 0))
+        .await;
+    assert!(hover.is_null());
+}
+
+#[tokio::test]
+async fn closed_workspace_files_are_analysed_as_saved() {
+    let dir = std::env::temp_dir().join(format!("froggy-close-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.frog");
+    std::fs::write(&path, "PLOP 0\nLEAP nowhere\n").unwrap();
+    let folder = Url::from_directory_path(&dir).unwrap();
+    let uri = Url::from_file_path(&path).unwrap();
+
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({
+            "capabilities": {
+                "workspace": { "didChangeWatchedFiles": { "dynamicRegistration": true } }
+            },
+            "workspaceFolders": [{ "uri": folder, "name": "ws" }],
+        }))
+        .await;
+    // Unsaved edits are dropped when the editor closes the file
+    server.open(&uri, "PLOP 1\nRIBBIT\n").await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    server
+        .notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    assert_eq!(codes(&server.diagnostics(&uri).await), ["compile-error"]);
+
+    // The watcher keeps the saved copy current
+    let watched = |typ: u32| json!({ "changes": [{ "uri": uri, "type": typ }] });
+    std::fs::write(
+        &path,
+        "PLOP 0\nLEAP nowhere\nRIBBIT\nLILY nowhere\nRIBBIT\n",
+    )
+    .unwrap();
+    server
+        .notify("workspace/didChangeWatchedFiles", watched(2))
+        .await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    server
+        .notify("workspace/didChangeWatchedFiles", watched(3))
+        .await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+    let hover = server
+        .request("textDocument/hover", at(&uri, "PLOP", "PLOP", 0))
+        .await;
+    assert!(hover.is_null());
+}
+
+#[tokio::test]
+async fn closed_workspace_files_are_dropped_without_a_watcher() {
+    let dir = std::env::temp_dir().join(format!("froggy-unwatched-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("main.frog");
+    std::fs::write(&path, "PLOP 0\nLEAP nowhere\n").unwrap();
+    let folder = Url::from_directory_path(&dir).unwrap();
+    let uri = Url::from_file_path(&path).unwrap();
+
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({
+            "capabilities": {},
+            "workspaceFolders": [{ "uri": folder, "name": "ws" }],
+        }))
+        .await;
+    server.open(&uri, "PLOP 1\nRIBBIT\n").await;
+    assert!(server.diagnostics(&uri).await.is_empty());
+
+    server
+        .notify(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    let diags = server.diagnostics(&uri).await;
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(diags.is_empty());
+}
+
+// An edit arriving while the first parse runs cancels that parse; the document must
+// still count as open, or the edit is dropped and the file never analysed again
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn edits_after_a_failed_open_are_applied() {
+    let program = "PLOP 1\nRIBBIT\n";
+    let cases = [
+        (
+            "big.frog",
+            "froggy",
+            program.repeat(200_000),
+            "PLOP\n".to_string(),
+        ),
+        (
+            "big.md",
+            "markdown",
+            format!("```froggy\n{}```\n", program.repeat(200_000)),
+            "```froggy\nPLOP\n```\n".to_string(),
+        ),
+    ];
+    for (name, language, big, broken) in cases {
+        let mut server = TestServer::new();
+        server.initialize(json!({ "capabilities": {} })).await;
+        let uri = uri(name);
+
+        let open = json!({
+            "textDocument": { "uri": uri, "languageId": language, "version": 1, "text": big }
+        });
+        let change = json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{ "text": broken }]
+        });
+        let open = server.send("textDocument/didOpen", open, None).await;
+        let change = server.send("textDocument/didChange", change, None).await;
+        futures::join!(open, change);
+
+        let published = server
+            .notification("textDocument/publishDiagnostics", |p| p["version"] == 2)
+            .await;
+        assert_eq!(
+            published["diagnostics"].as_array().unwrap().len(),
+            1,
+            "{name}"
+        );
+
+        // And later edits keep being applied
+        let fixed = broken.replace("PLOP\n", program);
+        server.change(&uri, 3, &fixed).await;
+        let published = server
+            .notification("textDocument/publishDiagnostics", |p| p["version"] == 3)
+            .await;
+        assert_eq!(published["diagnostics"], json!([]), "{name}");
+    }
+}
+
+// The same for a notebook, whose cells have to be kept for the change to apply to
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn notebook_edits_after_a_failed_open_are_applied() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let notebook = "file:///fixtures/book.ipynb";
+    let edit = |version: i32, text: &str| {
+        json!({
+            "notebookDocument": { "uri": notebook, "version": version },
+            "change": { "cells": { "textContent": [{
+                "document": { "uri": cell(0), "version": version },
+                "changes": [{ "text": text }],
+            }] } },
+        })
+    };
+
+    let open = json!({
+        "notebookDocument": {
+            "uri": notebook,
+            "notebookType": "jupyter-notebook",
+            "version": 1,
+            "cells": [{ "kind": 2, "document": cell(0) }],
+        },
+        "cellTextDocuments": [{
+            "uri": cell(0), "languageId": "froggy", "version": 1,
+            "text": "PLOP 1\nRIBBIT\n".repeat(200_000)
+        }],
+    });
+    let open = server.send("notebookDocument/didOpen", open, None).await;
+    let change = server
+        .send("notebookDocument/didChange", edit(2, "PLOP\n"), None)
+        .await;
+    futures::join!(open, change);
+
+    let published = server
+        .notification("textDocument/publishDiagnostics", |p| p["version"] == 2)
+        .await;
+    assert_eq!(published["diagnostics"].as_array().unwrap().len(), 1);
+
+    server
+        .notify("notebookDocument/didChange", edit(3, "PLOP 1\nRIBBIT\n"))
+        .await;
+    let published = server
+        .notification("textDocument/publishDiagnostics", |p| p["version"] == 3)
+        .await;
+    assert_eq!(published["diagnostics"], json!([]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn requests_during_edits_always_see_a_document() {
+    let (mut server, uri, _) = server_with("countdown.frog").await;
+    let hover = json!({
+        "textDocument": { "uri": uri },
+        "position": { "line": 0, "character": 0 },
+    });
+
+    let mut edits = Vec::new();
+    let mut hovers = Vec::new();
+    for version in 2..60 {
+        let change = json!({
+            "textDocument": { "uri": uri, "version": version },
+            "contentChanges": [{ "text": format!("PLOP {version}\nRIBBIT\n") }]
+        });
+        edits.push(server.send("textDocument/didChange", change, None).await);
+        hovers.push(
+            server
+                .send("textDocument/hover", hover.clone(), Some(version.into()))
+                .await,
+        );
+    }
+
+    let (_, hovers) = futures::join!(
+        futures::future::join_all(edits),
+        futures::future::join_all(hovers)
+    );
+    for response in hovers {
+        let (_, result) = response.unwrap().into_parts();
+        assert!(!result.unwrap().is_null());
+    }
+
+    let published = server
+        .notification("textDocument/publishDiagnostics", |p| p["version"] == 59)
+        .await;
+    assert_eq!(published["diagnostics"], json!([]));
+}
+
+#[tokio::test]
+async fn misspelled_mnemonics_have_a_quick_fix() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("typo.frog");
+    server.open(&uri, "PLOP 1\nPLOP 2\nSWPA\n").await;
+
+    let diags = server.diagnostics(&uri).await;
+    let typo = diags
+        .iter()
+        .find(|d| d.message.contains("did you mean `SWAP`"))
+        .expect("typo diagnostic");
+
+    let actions = server
+        .request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": typo.range,
+                "context": { "diagnostics": [typo] },
+            }),
+        )
+        .await;
+    let actions: Vec<CodeAction> = serde_json::from_value(actions).unwrap();
+    assert_eq!(actions.len(), 1);
+    assert_eq!(actions[0].title, "Replace with `SWAP`");
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&uri];
+    assert_eq!(edits, &[TextEdit::new(typo.range, "SWAP".to_string())]);
+}
+
+// Titles and inserted text of the quick fixes for the diagnostic saying `message`
+async fn fixes_for(server: &mut TestServer, uri: &Url, message: &str) -> Vec<(String, String)> {
+    let diags = server.diagnostics(uri).await;
+    let diag = diags
+        .iter()
+        .find(|d| d.message == message)
+        .unwrap_or_else(|| panic!("no `{message}` in {diags:?}"));
+    let actions = server
+        .request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": diag.range,
+                "context": { "diagnostics": [diag] },
+            }),
+        )
+        .await;
+    let actions: Vec<CodeAction> = serde_json::from_value(actions).unwrap();
+    actions
+        .into_iter()
+        .map(|a| {
+            let edits = &a.edit.unwrap().changes.unwrap()[uri];
+            (a.title, edits[0].new_text.clone())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn incomplete_instructions_have_insert_fixes() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("incomplete.frog");
+
+    server.open(&uri, "LILY start\nPLOP\nRIBBIT\n").await;
+    assert_eq!(
+        fixes_for(&mut server, &uri, "Expected number after `PLOP`").await,
+        [("Insert value `0`".to_string(), " 0".to_string())]
+    );
+
+    server
+        .change(&uri, 2, "LILY start\nPLOP 1\nLILY end\nSPLASH\nLEAP")
+        .await;
+    assert_eq!(
+        fixes_for(&mut server, &uri, "Expected label name after `LEAP`").await,
+        [
+            ("Insert target `start`".to_string(), " start".to_string()),
+            ("Insert target `end`".to_string(), " end".to_string()),
+        ]
+    );
+
+    // In a notebook, the operand goes at the end of the instruction in its own cell
+    let mut server = notebook_server().await;
+    server
+        .notify(
+            "notebookDocument/didChange",
+            json!({
+                "notebookDocument": { "uri": "file:///fixtures/book.ipynb", "version": 2 },
+                "change": { "cells": { "textContent": [{
+                    "document": { "uri": cell(2), "version": 2 },
+                    "changes": [{ "text": "RIBBIT\nPLOP" }],
+                }] } },
+            }),
+        )
+        .await;
+    let diags = server
+        .notification("textDocument/publishDiagnostics", |p| {
+            p["uri"] == cell(2).as_str() && p["version"] == 2
+        })
+        .await;
+    let diag = diags["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["message"] == "Expected number after `PLOP`")
+        .expect("incomplete PLOP");
+    let actions = server
+        .request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": cell(2) },
+                "range": diag["range"],
+                "context": { "diagnostics": [diag] },
+            }),
+        )
+        .await;
+    let actions: Vec<CodeAction> = serde_json::from_value(actions).unwrap();
+    let edits = &actions[0].edit.as_ref().unwrap().changes.as_ref().unwrap()[&cell(2)];
+    assert_eq!(
+        edits,
+        &[TextEdit::new(
+            Range::new(Position::new(1, 4), Position::new(1, 4)),
+            " 0".to_string()
+        )]
+    );
+}
+
+#[tokio::test]
+async fn insert_fixes_are_snippets_when_supported() {
+    let mut server = TestServer::new();
+    server
+        .initialize(json!({ "capabilities": {
+            "workspace": { "workspaceEdit": { "snippetEditSupport": true } }
+        } }))
+        .await;
+    let uri = uri("incomplete.frog");
+    server
+        .open(&uri, "LILY start\nPLOP 1\nLILY end\nSPLASH\nLEAP")
+        .await;
+
+    let diags = server.diagnostics(&uri).await;
+    let diag = diags
+        .iter()
+        .find(|d| d.message == "Expected label name after `LEAP`")
+        .unwrap();
+    let actions = server
+        .request(
+            "textDocument/codeAction",
+            json!({
+                "textDocument": { "uri": uri },
+                "range": diag.range,
+                "context": { "diagnostics": [diag] },
+            }),
+        )
+        .await;
+
+    // One fix, offering the labels as a choice
+    assert_eq!(actions.as_array().unwrap().len(), 1);
+    assert_eq!(actions[0]["title"], "Insert target");
+    let change = &actions[0]["edit"]["documentChanges"][0];
+    assert_eq!(change["textDocument"]["uri"], uri.as_str());
+    assert_eq!(
+        change["edits"][0],
+        json!({
+            "range": { "start": { "line": 4, "character": 4 }, "end": { "line": 4, "character": 4 } },
+            "snippet": { "kind": "snippet", "value": " ${1|start,end|}" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn stack_effects_show_in_hover_and_completion() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("annotated.frog");
+    let text = "PLOP 3\n// ( n -- n )\nLILY loop\nBURP\nDUP\nLEAP done\nLILY done\nHOP ";
+    server.open(&uri, text).await;
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, text, "loop", 0))
+        .await;
+    assert_eq!(hover["contents"], "Label definition: loop ( n -- n )");
+
+    let items = server
+        .request(
+            "textDocument/completion",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 7, "character": 4 } }),
+        )
+        .await;
+    let items: Vec<CompletionItem> = serde_json::from_value(items).unwrap();
+    let labels: Vec<_> = items
+        .iter()
+        .map(|i| (i.label.as_str(), i.detail.as_deref()))
+        .collect();
+    assert_eq!(labels, [("loop", Some("( n -- n )")), ("done", None)]);
+}
+
+#[tokio::test]
+async fn doc_comments_show_in_hover_completion_and_symbols() {
+    let mut server = TestServer::new();
+    server.initialize(json!({ "capabilities": {} })).await;
+    let uri = uri("documented.frog");
+    let text = "PLOP 3\n// Loops until the counter hits *zero*.\nLILY loop\nBURP\nDUP\nLEAP done\n\
+                LILY done\nHOP ";
+    server.open(&uri, text).await;
+
+    let hover = server
+        .request("textDocument/hover", at(&uri, text, "loop", 0))
+        .await;
+    let hover: Hover = serde_json::from_value(hover).unwrap();
+    assert_eq!(
+        hover.contents,
+        HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: "Label definition: loop\n\nLoops until the counter hits *zero*.".to_string(),
+        })
+    );
+
+    let items = server
+        .request(
+            "textDocument/completion",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 7, "character": 4 } }),
+        )
+        .await;
+    let items: Vec<CompletionItem> = serde_json::from_value(items).unwrap();
+    assert!(matches!(
+        &items[0].documentation,
+        Some(Documentation::MarkupContent(m)) if m.value == "Loops until the counter hits *zero*."
+    ));
+    assert_eq!(items[1].documentation, None);
+
+    let symbols = server
+        .request(
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": uri } }),
+        )
+        .await;
+    let symbols: Vec<DocumentSymbol> = serde_json::from_value(symbols).unwrap();
+    let detail = |name: &str| {
+        let symbol = symbols.iter().find(|s| s.name == name).unwrap();
+        symbol.detail.clone().unwrap()
+    };
+    assert_eq!(detail("loop"), "Loops until the counter hits *zero*.");
+    assert_eq!(detail("done"), "Label");
+}
@@ -0,0 +1,21 @@
+mod common;
+
+use std::sync::atomic::AtomicBool;
+
+use common::versioned_doc;
+use lsp_froggy::document::{ParseError, parse_text};
+
+#[test]
+fn parse_succeeds_without_cancellation() {
+    let doc = versioned_doc("PLOP 1\nRIBBIT\n", 3);
+    assert_eq!(doc.version, 3);
+    assert!(!doc.tree.root_node().has_error());
+}
+
+#[test]
+fn cancelled_parse_returns_no_tree() {
+    // Large enough that tree-sitter checks the flag before finishing
+    let text = "PLOP 1\nSPLASH\n".repeat(10_000);
+    let result = parse_text(&text, &AtomicBool::new(true));
+    assert!(matches!(result, Err(ParseError::Cancelled)));
+}
@@ -0,0 +1,25 @@
+use lsp_froggy::project::parse_config;
+use tower_lsp::lsp_types::{Position, Range};
+
+#[test]
+fn config_problems_are_located_in_utf16() {
+    // `á` and `é` are two bytes in UTF-8 but one UTF-16 code unit each
+    let (_, diags) = parse_config("[lints]\n\"cáfé\" = \"off\"\nunused-label = \"lóud\"\n");
+    let ranges: Vec<(Range, &str)> = diags
+        .iter()
+        .map(|d| (d.range, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        ranges,
+        [
+            (
+                Range::new(Position::new(1, 0), Position::new(1, 6)),
+                "Unknown lint rule `cáfé`"
+            ),
+            (
+                Range::new(Position::new(2, 15), Position::new(2, 21)),
+                "Expected one of \"off\", \"hint\", \"info\", \"warning\", \"error\""
+            ),
+        ]
+    );
+}
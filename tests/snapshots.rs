@@ -0,0 +1,159 @@
+//! Golden-file tests: every fixture in `tests/fixtures` is rendered with its semantic
+//! tokens and diagnostics as inline annotations and compared against
+//! `tests/snapshots/<name>.snap`. Run with `BLESS=1` to write the current output.
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{doc, fixture};
+use lsp_froggy::diagnostics::collect_diagnostics;
+use lsp_froggy::document::Doc;
+use lsp_froggy::lint::LintConfig;
+use lsp_froggy::semantic_tokens::{build_semantic_tokens, encode_semantic_tokens, legend};
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString};
+
+struct Annotation {
+    line: u32,
+    col: u32,
+    len: u32,
+    label: String,
+}
+
+fn token_annotations(doc: &Doc) -> Vec<Annotation> {
+    let legend = legend();
+    let tokens = encode_semantic_tokens(build_semantic_tokens(doc));
+
+    let (mut line, mut col) = (0, 0);
+    let mut out = Vec::new();
+    for t in tokens.data {
+        if t.delta_line > 0 {
+            line += t.delta_line;
+            col = t.delta_start;
+        } else {
+            col += t.delta_start;
+        }
+
+        let mut label = legend.token_types[t.token_type as usize]
+            .as_str()
+            .to_string();
+        for (bit, modifier) in legend.token_modifiers.iter().enumerate() {
+            if t.token_modifiers_bitset & (1 << bit) != 0 {
+                label.push('.');
+                label.push_str(modifier.as_str());
+            }
+        }
+        out.push(Annotation {
+            line,
+            col,
+            len: t.length,
+            label,
+        });
+    }
+    out
+}
+
+fn diagnostic_annotation(d: &Diagnostic, lines: &[&str]) -> Annotation {
+    let severity = match d.severity {
+        Some(DiagnosticSeverity::ERROR) => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(DiagnosticSeverity::INFORMATION) => "info",
+        _ => "hint",
+    };
+    let code = match &d.code {
+        Some(NumberOrString::String(code)) => format!("[{code}]"),
+        _ => String::new(),
+    };
+
+    // Multi-line ranges are underlined to the end of their first line
+    let (start, end) = (d.range.start, d.range.end);
+    let end_col = if end.line == start.line {
+        end.character
+    } else {
+        lines.get(start.line as usize).map_or(0, |l| l.len() as u32)
+    };
+
+    Annotation {
+        line: start.line,
+        col: start.character,
+        len: end_col.saturating_sub(start.character),
+        label: format!("{severity}{code}: {}", d.message),
+    }
+}
+
+fn render(text: &str) -> String {
+    let doc = doc(text);
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut annotations = token_annotations(&doc);
+    annotations.extend(
+        collect_diagnostics(&doc.tree, &doc, &LintConfig::default())
+            .iter()
+            .map(|d| diagnostic_annotation(d, &lines)),
+    );
+    annotations.sort_by(|a, b| (a.line, a.col, &a.label).cmp(&(b.line, b.col, &b.label)));
+
+    let mut out = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+        for a in annotations.iter().filter(|a| a.line as usize == i) {
+            out.push_str(&" ".repeat(a.col as usize));
+            out.push_str(&"^".repeat(a.len.max(1) as usize));
+            out.push(' ');
+            out.push_str(&a.label);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn snapshot_path(fixture: &Path) -> PathBuf {
+    let name = fixture.file_stem().unwrap().to_string_lossy();
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.snap"))
+}
+
+#[test]
+fn fixtures_match_snapshots() {
+    let bless = env::var_os("BLESS").is_some();
+    let fixtures_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&fixtures_dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "frog"))
+        .collect();
+    fixtures.sort();
+
+    let mut failures = Vec::new();
+    for fixture in &fixtures {
+        let name = fixture.file_name().unwrap().to_string_lossy();
+        let actual = render(&self::fixture(&name));
+        let snapshot = snapshot_path(fixture);
+
+        if bless {
+            fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+            fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+
+        match fs::read_to_string(&snapshot) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{} does not match:\n--- expected\n{expected}\n--- actual\n{actual}",
+                snapshot.display()
+            )),
+            Err(_) => failures.push(format!("{} is missing", snapshot.display())),
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{}\n\nRun with BLESS=1 to update snapshots",
+        failures.join("\n\n")
+    );
+}
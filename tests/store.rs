@@ -0,0 +1,72 @@
+mod common;
+
+use std::sync::Arc;
+use std::thread;
+
+use common::versioned_doc;
+use lsp_froggy::document::Doc;
+use lsp_froggy::store::DocStore;
+use tower_lsp::lsp_types::Url;
+
+fn doc(version: i32) -> Doc {
+    versioned_doc(&format!("PLOP {version}\nRIBBIT\n"), version)
+}
+
+#[test]
+fn older_versions_do_not_replace_newer_ones() {
+    let store = DocStore::default();
+    let uri = Url::parse("file:///a.frog").unwrap();
+
+    store.insert(uri.clone(), doc(3));
+    assert!(!store.update(uri.clone(), doc(2)));
+    assert_eq!(store.version(&uri), Some(3));
+    assert!(store.update(uri.clone(), doc(4)));
+
+    // A snapshot taken before an update stays as it was
+    let snapshot = store.get(&uri).unwrap();
+    store.update(uri.clone(), doc(5));
+    assert_eq!(snapshot.version, 4);
+    assert_eq!(snapshot.text, "PLOP 4\nRIBBIT\n");
+}
+
+#[test]
+fn readers_see_consistent_snapshots_while_writers_swap() {
+    let store = Arc::new(DocStore::default());
+    let uri = Url::parse("file:///a.frog").unwrap();
+    store.insert(uri.clone(), doc(1));
+
+    let writers: Vec<_> = (0..2)
+        .map(|w| {
+            let (store, uri) = (store.clone(), uri.clone());
+            thread::spawn(move || {
+                for version in (2..200).filter(|v| v % 2 == w) {
+                    store.update(uri.clone(), doc(version));
+                }
+            })
+        })
+        .collect();
+    let readers: Vec<_> = (0..4)
+        .map(|_| {
+            let (store, uri) = (store.clone(), uri.clone());
+            thread::spawn(move || {
+                let mut last = 0;
+                for _ in 0..2000 {
+                    let snapshot = store.get(&uri).expect("document went missing");
+                    // Text, tree and index all belong to the same version
+                    assert_eq!(
+                        snapshot.text,
+                        format!("PLOP {}\nRIBBIT\n", snapshot.version)
+                    );
+                    assert!(!snapshot.tree.root_node().has_error());
+                    assert!(snapshot.version >= last, "version went backwards");
+                    last = snapshot.version;
+                }
+            })
+        })
+        .collect();
+
+    for t in writers.into_iter().chain(readers) {
+        t.join().unwrap();
+    }
+    assert_eq!(store.version(&uri), Some(199));
+}
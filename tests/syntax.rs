@@ -0,0 +1,102 @@
+mod common;
+
+use common::doc;
+use lsp_froggy::syntax::{Operand, syntax_errors};
+
+fn errors(text: &str) -> Vec<(String, String)> {
+    let doc = doc(text);
+    syntax_errors(&doc.tree, &doc.text)
+        .into_iter()
+        .map(|e| (text[e.range.start..e.range.end].to_string(), e.message))
+        .collect()
+}
+
+fn one(source: &str, message: &str) -> Vec<(String, String)> {
+    vec![(source.to_string(), message.to_string())]
+}
+
+#[test]
+fn incomplete_instructions_say_what_is_missing() {
+    assert_eq!(
+        errors("HOP\n"),
+        one("HOP", "Expected label name after `HOP`")
+    );
+    assert_eq!(
+        errors("PLOP\nRIBBIT\n"),
+        one("PLOP", "Expected number or string after `PLOP`")
+    );
+    assert_eq!(
+        errors("RIBBIT\nHOP"),
+        one("", "Expected label name after `HOP`")
+    );
+}
+
+#[test]
+fn unexpected_tokens_list_what_was_expected() {
+    assert_eq!(
+        errors("LEAP 3\n"),
+        one(
+            "3",
+            "Unexpected number `3`; expected label name after `LEAP`"
+        )
+    );
+    assert_eq!(
+        errors("RIBBIT 5\n"),
+        one("5", "Unexpected number `5`; expected instruction")
+    );
+    assert_eq!(
+        errors("PLOP \"abc\n"),
+        one(
+            "\"abc\n",
+            "Unexpected `\"abc`; expected number or string after `PLOP`"
+        )
+    );
+}
+
+#[test]
+fn nested_errors_are_reported_once() {
+    assert_eq!(
+        errors("PLOP 1\nribbit\n"),
+        one(
+            "ribbit",
+            "Mnemonics are uppercase: `ribbit` must be written as `RIBBIT`"
+        )
+    );
+}
+
+fn suggest(text: &str) -> (&str, Option<&'static str>) {
+    let doc = doc(text);
+    let errors = syntax_errors(&doc.tree, &doc.text);
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    let e = &errors[0];
+    (&text[e.range.start..e.range.end], e.suggestion)
+}
+
+#[test]
+fn misspelled_mnemonics_get_suggestions() {
+    assert_eq!(suggest("PLOP 1\nSWPA\n"), ("SWPA", Some("SWAP")));
+    assert_eq!(suggest("ribit\n"), ("ribit", Some("RIBBIT")));
+    // Mnemonics aren't delimited, so these lex as a mnemonic plus a stray letter
+    assert_eq!(suggest("PLOP 1\nPLOPP 2\n"), ("PLOPP", Some("PLOP")));
+    assert_eq!(suggest("PLOP 1\nADDD\n"), ("ADDD", Some("ADD")));
+    assert_eq!(suggest("FROG\n"), ("FROG", None));
+
+    assert_eq!(
+        errors("SWPA\n"),
+        one("SWPA", "Unknown instruction `SWPA`, did you mean `SWAP`?")
+    );
+}
+
+#[test]
+fn incomplete_instructions_know_their_missing_operand() {
+    let insertion = |text: &str| {
+        let doc = doc(text);
+        let errors = syntax_errors(&doc.tree, &doc.text);
+        errors[0].insertion.clone().map(|i| (i.at, i.operand))
+    };
+
+    assert_eq!(insertion("PLOP\nRIBBIT\n"), Some((4, Operand::Value)));
+    assert_eq!(insertion("RIBBIT\nLEAP"), Some((11, Operand::Target)));
+    assert_eq!(insertion("LILY\n"), Some((4, Operand::LabelName)));
+    assert_eq!(insertion("RIBBIT 5\n"), None);
+}
@@ -0,0 +1,138 @@
+use lsp_froggy::logging::Logger;
+use lsp_froggy::transport::serve_tcp;
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+struct Client {
+    read: BufReader<OwnedReadHalf>,
+    write: OwnedWriteHalf,
+}
+
+impl Client {
+    async fn connect(port: u16) -> Self {
+        let (read, write) = TcpStream::connect(("127.0.0.1", port))
+            .await
+            .unwrap()
+            .into_split();
+        Self {
+            read: BufReader::new(read),
+            write,
+        }
+    }
+
+    async fn send(&mut self, message: Value) {
+        let body = message.to_string();
+        let frame = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        self.write.write_all(frame.as_bytes()).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Value {
+        let mut len = 0;
+        loop {
+            let mut line = String::new();
+            self.read.read_line(&mut line).await.unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(n) = line.strip_prefix("Content-Length: ") {
+                len = n.parse().unwrap();
+            }
+        }
+        let mut body = vec![0; len];
+        self.read.read_exact(&mut body).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    // Read until the response to request `id`, skipping notifications
+    async fn response(&mut self, id: i64) -> Value {
+        loop {
+            let message = self.recv().await;
+            if message["id"] == id && message.get("method").is_none() {
+                return message;
+            }
+        }
+    }
+
+    async fn initialize(&mut self) -> Value {
+        self.send(json!({
+            "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": { "capabilities": {} }
+        }))
+        .await;
+        self.response(1).await
+    }
+}
+
+#[tokio::test]
+async fn tcp_listener_serves_several_clients() {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(serve_tcp(listener, Logger::default()));
+
+    let mut first = Client::connect(port).await;
+    let mut second = Client::connect(port).await;
+    assert!(first.initialize().await["result"]["capabilities"].is_object());
+    assert!(second.initialize().await["result"]["capabilities"].is_object());
+
+    // One client going away leaves the others connected
+    drop(first);
+    second
+        .send(json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }))
+        .await;
+    assert_eq!(second.response(2).await["result"], Value::Null);
+
+    let mut third = Client::connect(port).await;
+    assert!(third.initialize().await["result"]["capabilities"].is_object());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn socket_path_holding_a_file_is_left_alone() {
+    use lsp_froggy::transport::{Transport, serve};
+
+    let path = std::env::temp_dir().join(format!("froggy-socket-{}", std::process::id()));
+    std::fs::write(&path, "not a socket").unwrap();
+
+    let err = serve(Transport::Socket(path.clone()), Logger::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "not a socket");
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn live_sockets_are_kept_and_stale_ones_replaced() {
+    use lsp_froggy::transport::{Transport, serve};
+    use tokio::net::{UnixListener, UnixStream};
+
+    let path = std::env::temp_dir().join(format!("froggy-live-socket-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    // Another server is listening
+    let live = UnixListener::bind(&path).unwrap();
+    let err = serve(Transport::Socket(path.clone()), Logger::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(UnixStream::connect(&path).await.is_ok());
+
+    // It went away without removing its socket
+    drop(live);
+    assert!(path.exists());
+    let server = tokio::spawn(serve(Transport::Socket(path.clone()), Logger::default()));
+    let mut connected = false;
+    for _ in 0..100 {
+        if UnixStream::connect(&path).await.is_ok() {
+            connected = true;
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    server.abort();
+    let _ = std::fs::remove_file(&path);
+    assert!(connected);
+}